@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::memory::ADDR_INIT;
+use super::opcodes::{Instruction, Reg};
+
+// Source syntax, one statement per line:
+//
+//   ; comment
+//   loop:                  label, may be followed by a statement on the same line
+//   0x21A: CLS             listing address as printed by the disassembler (ignored)
+//   define SPEED 4         constant, usable wherever a number is
+//   db 0x80, 0x40, 0b1     bytes
+//   dw 0x1234              big-endian words
+//   include "sprites.8o"   relative to the including file
+//   DRW V1, V2, 5          any mnemonic documented on the Instruction enum
+//
+// Numbers are decimal, 0x hex or 0b binary. Mnemonics, registers and directives are
+// case-insensitive; labels and constants are not.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.col, self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    col: usize,
+}
+
+#[derive(Debug)]
+struct Line {
+    file: String,
+    number: usize,
+    tokens: Vec<Token>,
+}
+
+impl Line {
+    fn error(&self, col: usize, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.number,
+            col,
+            message: message.into(),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == ',' || c == '-' {
+            tokens.push(Token {
+                text: c.to_string(),
+                col,
+            });
+            i += 1;
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            tokens.push(Token {
+                text: chars[start..i].iter().collect(),
+                col,
+            });
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && !",;-\"".contains(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token {
+                text: chars[start..i].iter().collect(),
+                col,
+            });
+        }
+    }
+
+    tokens
+}
+
+// Reads a source file into lines, splicing in included files
+fn load(
+    source: &str,
+    file: &str,
+    dir: &Path,
+    depth: usize,
+    lines: &mut Vec<Line>,
+) -> Result<(), AsmError> {
+    for (i, text) in source.lines().enumerate() {
+        let line = Line {
+            file: file.to_string(),
+            number: i + 1,
+            tokens: tokenize(text),
+        };
+
+        let is_include = line
+            .tokens
+            .first()
+            .is_some_and(|t| t.text.eq_ignore_ascii_case("include"));
+        if !is_include {
+            lines.push(line);
+            continue;
+        }
+
+        let name = match line.tokens.get(1) {
+            Some(t) if t.text.len() >= 2 && t.text.starts_with('"') && t.text.ends_with('"') => {
+                &t.text[1..t.text.len() - 1]
+            }
+            Some(t) => return Err(line.error(t.col, "expected a quoted file name")),
+            None => return Err(line.error(1, "expected a quoted file name")),
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(line.error(1, "includes nested too deeply"));
+        }
+
+        let path = dir.join(name);
+        let included = fs::read_to_string(&path)
+            .map_err(|err| line.error(line.tokens[1].col, format!("{}: {}", name, err)))?;
+        let included_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        load(
+            &included,
+            &path.display().to_string(),
+            &included_dir,
+            depth + 1,
+            lines,
+        )?;
+    }
+
+    Ok(())
+}
+
+enum Operand<'a> {
+    V(Reg),
+    // I, DT, ST, K, F, HF, B, R, [I], or "long" for LD I, long NNNN
+    Name(String),
+    Value(&'a Token),
+    Long(&'a Token),
+}
+
+fn register(text: &str) -> Option<Reg> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok().map(Reg)
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+struct Assembler {
+    symbols: HashMap<String, u32>,
+}
+
+impl Assembler {
+    fn value(&self, line: &Line, token: &Token, max: u32) -> Result<u16, AsmError> {
+        let value = match parse_number(&token.text) {
+            Some(n) => n,
+            None => *self
+                .symbols
+                .get(&token.text)
+                .ok_or_else(|| line.error(token.col, format!("unknown symbol `{}`", token.text)))?,
+        };
+
+        if value > max {
+            return Err(line.error(
+                token.col,
+                format!("{:#X} does not fit in {:#X}", value, max),
+            ));
+        }
+        Ok(value as u16)
+    }
+
+    // Splits the tokens after the mnemonic into operands separated by `sep`
+    fn operands<'a>(
+        &self,
+        line: &'a Line,
+        tokens: &'a [Token],
+        sep: &[&str],
+    ) -> Result<Vec<Operand<'a>>, AsmError> {
+        let mut operands = Vec::new();
+        let mut expect_operand = true;
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let text = token.text.as_str();
+
+            if !expect_operand {
+                if !sep.contains(&text) {
+                    return Err(line.error(token.col, format!("expected `{}`", sep[0])));
+                }
+                expect_operand = true;
+                i += 1;
+                continue;
+            }
+
+            let upper = text.to_ascii_uppercase();
+            let operand = if let Some(r) = register(text) {
+                Operand::V(r)
+            } else if upper == "LONG" {
+                i += 1;
+                let value = tokens
+                    .get(i)
+                    .ok_or_else(|| line.error(token.col, "expected an address after `long`"))?;
+                Operand::Long(value)
+            } else if ["I", "DT", "ST", "K", "F", "HF", "B", "R", "[I]"].contains(&upper.as_str()) {
+                Operand::Name(upper)
+            } else {
+                Operand::Value(token)
+            };
+
+            operands.push(operand);
+            expect_operand = false;
+            i += 1;
+        }
+
+        if expect_operand && !tokens.is_empty() {
+            let last = tokens.last().unwrap();
+            return Err(line.error(last.col, "expected an operand"));
+        }
+        Ok(operands)
+    }
+
+    fn instruction(&self, line: &Line, tokens: &[Token]) -> Result<Instruction, AsmError> {
+        let mnemonic = tokens[0].text.to_ascii_uppercase();
+        let sep: &[&str] = if mnemonic == "SAVE" || mnemonic == "LOAD" {
+            &["-", ","]
+        } else {
+            &[","]
+        };
+        let ops = self.operands(line, &tokens[1..], sep)?;
+
+        use Instruction::*;
+        use Operand::{Long, Value, V};
+        let name = |op: &Operand, n: &str| matches!(op, Operand::Name(s) if s == n);
+        let byte = |t: &Token| self.value(line, t, 0xFF).map(|v| v as u8);
+        let nibble = |t: &Token| self.value(line, t, 0xF).map(|v| v as u8);
+        let addr = |t: &Token| self.value(line, t, 0xFFF);
+
+        let instruction = match (mnemonic.as_str(), ops.as_slice()) {
+            ("CLS", []) => Cls,
+            ("RET", []) => Ret,
+            ("SCD", [Value(n)]) => ScrollDown { n: nibble(n)? },
+            ("SCU", [Value(n)]) => ScrollUp { n: nibble(n)? },
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Low,
+            ("HIGH", []) => High,
+            ("JP", [Value(a)]) => Jump { nnn: addr(a)? },
+            ("JP", [V(Reg(0)), Value(a)]) => JumpV0 { nnn: addr(a)? },
+            ("CALL", [Value(a)]) => Call { nnn: addr(a)? },
+            ("SE", [V(x), Value(kk)]) => SkipEqImm {
+                x: *x,
+                kk: byte(kk)?,
+            },
+            ("SNE", [V(x), Value(kk)]) => SkipNeImm {
+                x: *x,
+                kk: byte(kk)?,
+            },
+            ("SE", [V(x), V(y)]) => SkipEq { x: *x, y: *y },
+            ("SAVE", [V(x), V(y)]) => SaveRange { x: *x, y: *y },
+            ("LOAD", [V(x), V(y)]) => LoadRange { x: *x, y: *y },
+            ("LD", [V(x), Value(kk)]) => LoadImm {
+                x: *x,
+                kk: byte(kk)?,
+            },
+            ("ADD", [V(x), Value(kk)]) => AddImm {
+                x: *x,
+                kk: byte(kk)?,
+            },
+            ("LD", [V(x), V(y)]) => Load { x: *x, y: *y },
+            ("OR", [V(x), V(y)]) => Or { x: *x, y: *y },
+            ("AND", [V(x), V(y)]) => And { x: *x, y: *y },
+            ("XOR", [V(x), V(y)]) => Xor { x: *x, y: *y },
+            ("ADD", [V(x), V(y)]) => Add { x: *x, y: *y },
+            ("SUB", [V(x), V(y)]) => Sub { x: *x, y: *y },
+            // Without Vy, shift Vx in place under either shift quirk
+            ("SHR", [V(x)]) => ShiftRight { x: *x, y: *x },
+            ("SHR", [V(x), V(y)]) => ShiftRight { x: *x, y: *y },
+            ("SUBN", [V(x), V(y)]) => SubN { x: *x, y: *y },
+            ("SHL", [V(x)]) => ShiftLeft { x: *x, y: *x },
+            ("SHL", [V(x), V(y)]) => ShiftLeft { x: *x, y: *y },
+            ("SNE", [V(x), V(y)]) => SkipNe { x: *x, y: *y },
+            ("LD", [i, Value(a)]) if name(i, "I") => LoadI { nnn: addr(a)? },
+            ("LD", [i, Long(a)]) if name(i, "I") => LoadILong {
+                nnnn: self.value(line, a, 0xFFFF)?,
+            },
+            ("RND", [V(x), Value(kk)]) => Random {
+                x: *x,
+                kk: byte(kk)?,
+            },
+            ("DRW", [V(x), V(y), Value(n)]) => Draw {
+                x: *x,
+                y: *y,
+                n: nibble(n)?,
+            },
+            ("SKP", [V(x)]) => SkipKey { x: *x },
+            ("SKNP", [V(x)]) => SkipNotKey { x: *x },
+            ("PLANE", [Value(n)]) => Plane { n: nibble(n)? },
+            ("AUDIO", []) => Audio,
+            ("LD", [V(x), dt]) if name(dt, "DT") => LoadDelay { x: *x },
+            ("LD", [V(x), k]) if name(k, "K") => WaitKey { x: *x },
+            ("LD", [dt, V(x)]) if name(dt, "DT") => SetDelay { x: *x },
+            ("LD", [st, V(x)]) if name(st, "ST") => SetSound { x: *x },
+            ("ADD", [i, V(x)]) if name(i, "I") => AddI { x: *x },
+            ("LD", [f, V(x)]) if name(f, "F") => LoadFont { x: *x },
+            ("LD", [hf, V(x)]) if name(hf, "HF") => LoadBigFont { x: *x },
+            ("LD", [b, V(x)]) if name(b, "B") => Bcd { x: *x },
+            ("PITCH", [V(x)]) => Pitch { x: *x },
+            ("LD", [i, V(x)]) if name(i, "[I]") => Store { x: *x },
+            ("LD", [V(x), i]) if name(i, "[I]") => Restore { x: *x },
+            ("LD", [r, V(x)]) if name(r, "R") => SaveFlags { x: *x },
+            ("LD", [V(x), r]) if name(r, "R") => LoadFlags { x: *x },
+            (
+                "CLS" | "RET" | "SCD" | "SCU" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "JP"
+                | "CALL" | "SE" | "SNE" | "SAVE" | "LOAD" | "LD" | "ADD" | "OR" | "AND" | "XOR"
+                | "SUB" | "SHR" | "SUBN" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE"
+                | "AUDIO" | "PITCH",
+                _,
+            ) => {
+                return Err(line.error(
+                    tokens[0].col,
+                    format!("invalid operands for {}", tokens[0].text),
+                ))
+            }
+            _ => {
+                return Err(line.error(
+                    tokens[0].col,
+                    format!("unknown instruction `{}`", tokens[0].text),
+                ))
+            }
+        };
+
+        Ok(instruction)
+    }
+
+    fn data(&self, line: &Line, tokens: &[Token], width: usize) -> Result<Vec<u8>, AsmError> {
+        let max = if width == 1 { 0xFF } else { 0xFFFF };
+        let mut out = Vec::new();
+
+        for op in self.operands(line, &tokens[1..], &[","])? {
+            let Operand::Value(token) = op else {
+                return Err(line.error(tokens[0].col, "expected numbers"));
+            };
+            let value = self.value(line, token, max)?;
+            if width == 1 {
+                out.push(value as u8);
+            } else {
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+
+        Ok(out)
+    }
+
+    // Returns the tokens of the statement after any labels or listing addresses,
+    // defining the labels at addr when `define_labels` is set
+    fn statement<'a>(
+        &mut self,
+        line: &'a Line,
+        addr: u32,
+        define_labels: bool,
+    ) -> Result<&'a [Token], AsmError> {
+        let mut tokens = line.tokens.as_slice();
+
+        while let Some(name) = tokens.first().and_then(|t| t.text.strip_suffix(':')) {
+            if parse_number(name).is_none() && define_labels {
+                if name.is_empty() || register(name).is_some() {
+                    return Err(line.error(tokens[0].col, format!("invalid label `{}`", name)));
+                }
+                if self.symbols.insert(name.to_string(), addr).is_some() {
+                    return Err(line.error(tokens[0].col, format!("duplicate symbol `{}`", name)));
+                }
+            }
+            tokens = &tokens[1..];
+        }
+
+        Ok(tokens)
+    }
+
+    fn define(&mut self, line: &Line, tokens: &[Token]) -> Result<(), AsmError> {
+        let (Some(name), Some(value), None) = (tokens.get(1), tokens.get(2), tokens.get(3)) else {
+            return Err(line.error(tokens[0].col, "expected `define NAME value`"));
+        };
+
+        let value = self.value(line, value, 0xFFFF)? as u32;
+        if self.symbols.insert(name.text.clone(), value).is_some() {
+            return Err(line.error(name.col, format!("duplicate symbol `{}`", name.text)));
+        }
+        Ok(())
+    }
+
+    fn size(&self, tokens: &[Token]) -> usize {
+        let first = tokens[0].text.to_ascii_lowercase();
+        let values = tokens[1..].iter().filter(|t| t.text != ",").count();
+
+        match first.as_str() {
+            "db" => values,
+            "dw" => 2 * values,
+            _ if tokens.iter().any(|t| t.text.eq_ignore_ascii_case("long")) => 4,
+            _ => 2,
+        }
+    }
+}
+
+fn assemble_lines(lines: &[Line]) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler {
+        symbols: HashMap::new(),
+    };
+
+    // First pass: every statement has a fixed size, so label addresses are known
+    // before any operand is encoded
+    let mut addr = ADDR_INIT as u32;
+    for line in lines {
+        let tokens = asm.statement(line, addr, true)?;
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        if first.text.eq_ignore_ascii_case("define") {
+            asm.define(line, tokens)?;
+        } else {
+            addr += asm.size(tokens) as u32;
+        }
+    }
+
+    let mut rom = Vec::new();
+    for line in lines {
+        let tokens = asm.statement(line, 0, false)?;
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        match first.text.to_ascii_lowercase().as_str() {
+            "define" => {}
+            "db" => rom.extend(asm.data(line, tokens, 1)?),
+            "dw" => rom.extend(asm.data(line, tokens, 2)?),
+            _ => rom.extend(asm.instruction(line, tokens)?.encode()),
+        }
+
+        if ADDR_INIT as usize + rom.len() > 0x10000 {
+            return Err(line.error(first.col, "program does not fit in memory"));
+        }
+    }
+
+    Ok(rom)
+}
+
+// Assembles source text; includes are resolved relative to the current directory
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut lines = Vec::new();
+    load(source, "<input>", Path::new(""), 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|err| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        col: 0,
+        message: err.to_string(),
+    })?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut lines = Vec::new();
+    load(&source, &path.display().to_string(), &dir, 0, &mut lines)?;
+    assemble_lines(&lines)
+}
@@ -0,0 +1,120 @@
+use std::f32::consts::TAU;
+
+use super::cpu::TIMER_HZ;
+
+// Fade in/out over this long instead of switching the tone on and off, which clicks
+const RAMP_SECONDS: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    pub const NAMES: [&'static str; 3] = ["square", "sine", "triangle"];
+
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    // Hz
+    pub frequency: f32,
+    // 0.0-1.0
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+// Mono f32 samples of the sound timer's tone, shared by the SDL audio callback and
+// headless rendering
+#[derive(Debug, Clone)]
+pub struct ToneGenerator {
+    pub tone: Tone,
+    pub sample_rate: u32,
+    // Whether the tone should be sounding; the output ramps towards it
+    pub playing: bool,
+    // Position within the current wave period, 0.0-1.0
+    phase: f32,
+    // Envelope, 0.0-1.0
+    level: f32,
+    frame_remainder: u32,
+}
+
+impl ToneGenerator {
+    pub fn new(sample_rate: u32, tone: Tone) -> Self {
+        Self {
+            tone,
+            sample_rate,
+            playing: false,
+            phase: 0.0,
+            level: 0.0,
+            frame_remainder: 0,
+        }
+    }
+
+    fn sample(&mut self) -> f32 {
+        let step = 1.0 / (RAMP_SECONDS * self.sample_rate as f32);
+        self.level = if self.playing {
+            (self.level + step).min(1.0)
+        } else {
+            (self.level - step).max(0.0)
+        };
+
+        // Start every beep at the beginning of a period
+        if self.level == 0.0 {
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        let value = match self.tone.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+        };
+        self.phase = (self.phase + self.tone.frequency / self.sample_rate as f32).fract();
+
+        value * self.level * self.tone.volume
+    }
+
+    pub fn render(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.sample();
+        }
+    }
+
+    // Appends one 1/60 s frame of emulated time, carrying the remainder over so that
+    // exactly sample_rate samples are produced per emulated second
+    pub fn render_frame(&mut self, out: &mut Vec<f32>) {
+        let total = self.sample_rate + self.frame_remainder;
+        self.frame_remainder = total % TIMER_HZ;
+
+        let start = out.len();
+        out.resize(start + (total / TIMER_HZ) as usize, 0.0);
+        self.render(&mut out[start..]);
+    }
+}
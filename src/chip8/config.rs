@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+use super::audio::{Tone, Waveform};
+use super::error::Chip8Error;
+use super::keymap::KeyMap;
+use super::palette::Palette;
+use super::quirks::Quirks;
+
+// Every field is optional so a ROM override only needs the settings it changes, e.g.
+//
+//   scale = 12
+//   palette = "amber"
+//
+//   [keys]
+//   5 = ["W", "Up"]
+//
+//   [buttons]
+//   5 = ["dpup", "y"]
+//
+//   [roms."<sha1 of the ROM>"]
+//   quirks = "vip"
+//   ips = 1000
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ips: Option<u32>,
+    pub scale: Option<u32>,
+    #[serde(deserialize_with = "palette")]
+    pub palette: Option<Palette>,
+    #[serde(deserialize_with = "quirks")]
+    pub quirks: Option<Quirks>,
+    pub debugger: Option<bool>,
+    // CHIP-8 key (0x0-0xF) -> SDL scancode names of the keys that press it
+    #[serde(deserialize_with = "keys")]
+    pub keys: BTreeMap<u8, Vec<String>>,
+    // CHIP-8 key (0x0-0xF) -> SDL game controller button names
+    #[serde(deserialize_with = "keys")]
+    pub buttons: BTreeMap<u8, Vec<String>>,
+    // Analog stick deflection (out of 32767) needed to press a D-pad direction
+    pub dead_zone: Option<u16>,
+    // Sound timer tone: Hz, 0.0-1.0 and square/sine/triangle
+    pub frequency: Option<f32>,
+    pub volume: Option<f32>,
+    #[serde(deserialize_with = "waveform")]
+    pub waveform: Option<Waveform>,
+}
+
+impl Settings {
+    // Settings present in `other` win; keys are replaced per CHIP-8 key
+    pub fn merge(&mut self, other: &Settings) {
+        self.ips = other.ips.or(self.ips);
+        self.scale = other.scale.or(self.scale);
+        self.palette = other.palette.or(self.palette);
+        self.quirks = other.quirks.or(self.quirks);
+        self.debugger = other.debugger.or(self.debugger);
+        self.keys
+            .extend(other.keys.iter().map(|(k, v)| (*k, v.clone())));
+        self.buttons
+            .extend(other.buttons.iter().map(|(k, v)| (*k, v.clone())));
+        self.dead_zone = other.dead_zone.or(self.dead_zone);
+        self.frequency = other.frequency.or(self.frequency);
+        self.volume = other.volume.or(self.volume);
+        self.waveform = other.waveform.or(self.waveform);
+    }
+
+    pub fn tone(&self) -> Tone {
+        let default = Tone::default();
+        Tone {
+            frequency: self.frequency.unwrap_or(default.frequency),
+            volume: self.volume.unwrap_or(default.volume).clamp(0.0, 1.0),
+            waveform: self.waveform.unwrap_or(default.waveform),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Settings,
+    // Per-ROM overrides keyed by the ROM's SHA-1 as lowercase hex
+    pub roms: HashMap<String, Settings>,
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/chip8/config.toml, falling back to ~/.config/chip8/config.toml
+    pub fn path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(dir.join("chip8").join("config.toml"))
+    }
+
+    pub fn parse(text: &str) -> Result<Config, Chip8Error> {
+        toml::from_str(text).map_err(|err| Chip8Error::InvalidConfig(err.to_string()))
+    }
+
+    // A missing file is not an error and gives the defaults
+    pub fn load(path: &Path) -> Result<Config, Chip8Error> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|err| match err {
+                Chip8Error::InvalidConfig(msg) => {
+                    Chip8Error::InvalidConfig(format!("{}: {}", path.display(), msg))
+                }
+                err => err,
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // The defaults with the overrides for the ROM with this SHA-1 applied
+    pub fn settings_for(&self, rom_hash: &[u8; 20]) -> Settings {
+        let hex: String = rom_hash.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut settings = self.defaults.clone();
+        if let Some(overrides) = self.roms.get(&hex) {
+            settings.merge(overrides);
+        }
+        settings
+    }
+}
+
+// Replaces the top-level `[keys]` table, keeping the rest of the file's settings (but not
+// its comments)
+pub fn save_keys(path: &Path, keymap: &KeyMap) -> Result<(), Chip8Error> {
+    let mut table = match fs::read_to_string(path) {
+        Ok(text) => text
+            .parse::<toml::Table>()
+            .map_err(|err| Chip8Error::InvalidConfig(format!("{}: {}", path.display(), err)))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let keys = toml::Value::try_from(keymap.to_table())
+        .map_err(|err| Chip8Error::InvalidConfig(err.to_string()))?;
+    table.insert("keys".to_string(), keys);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, table.to_string())?;
+    Ok(())
+}
+
+fn palette<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Palette>, D::Error> {
+    let name = String::deserialize(d)?;
+    Palette::from_name(&name).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "unknown palette `{}`, expected one of {}",
+            name,
+            Palette::NAMES.join(", ")
+        ))
+    })
+}
+
+fn quirks<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Quirks>, D::Error> {
+    let name = String::deserialize(d)?;
+    Quirks::from_name(&name).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "unknown quirks preset `{}`, expected one of {}",
+            name,
+            Quirks::NAMES.join(", ")
+        ))
+    })
+}
+
+fn waveform<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Waveform>, D::Error> {
+    let name = String::deserialize(d)?;
+    Waveform::from_name(&name).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "unknown waveform `{}`, expected one of {}",
+            name,
+            Waveform::NAMES.join(", ")
+        ))
+    })
+}
+
+fn keys<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<u8, Vec<String>>, D::Error> {
+    let table = BTreeMap::<String, Vec<String>>::deserialize(d)?;
+
+    table
+        .into_iter()
+        .map(|(key, names)| match u8::from_str_radix(&key, 16) {
+            Ok(k) if k < 16 && key.len() == 1 => Ok((k, names)),
+            _ => Err(D::Error::custom(format!(
+                "`{}` is not a CHIP-8 key, expected 0-F",
+                key
+            ))),
+        })
+        .collect()
+}
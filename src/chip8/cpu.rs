@@ -1,12 +1,32 @@
-use crate::chip8::opcodes::Opcode;
-use crate::chip8::{display, memory, utils::rand_byte};
+use std::fs;
+#[cfg(feature = "sdl")]
+use std::path::Path;
 
-use super::memory::{FONTSET, FONTSET_ADDR_INIT};
+use sha1_smol::Sha1;
+
+use crate::chip8::memory;
+use crate::chip8::opcodes::{self, Instruction};
+
+#[cfg(feature = "sdl")]
+use crate::chip8::display;
+
+use super::debugger::{Access, Debugger};
+use super::error::Chip8Error;
+use super::memory::{BIG_FONTSET, BIG_FONTSET_ADDR_INIT, FONTSET, FONTSET_ADDR_INIT};
+use super::quirks::Quirks;
+use super::trace::{self, Tracer};
+use super::utils::{RandomSource, SplitMix64};
+use super::wav::WavRecorder;
+
+pub const DISPLAY_WIDTH: usize = 128;
+pub const DISPLAY_HEIGHT: usize = 64;
+pub const TIMER_HZ: u32 = 60;
+pub const DEFAULT_IPS: u32 = 700;
 
 #[derive(Debug)]
 pub struct Chip8 {
     pub reg: [u8; 16],
-    pub ram: [u8; 4096],
+    pub ram: [u8; memory::RAM_SIZE],
     pub ireg: u16,
     pub pc: u16,
     pub stack: [u16; 16],
@@ -14,15 +34,33 @@ pub struct Chip8 {
     pub dt: u8,
     pub st: u8,
     pub keys: [bool; 16],
-    pub display: [bool; 64 * 32],
+    pub display: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    pub plane: u8,
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+    pub hires: bool,
+    pub rpl: [u8; 16],
+    pub halted: bool,
     pub opcode: u16,
+    pub rom_hash: [u8; 20],
+    pub rom_size: usize,
+    pub quirks: Quirks,
+    pub vblank: bool,
+    pub ips: u32,
+    pub(crate) ips_remainder: u32,
+    pub(crate) frame_budget: u32,
+    pub cycles: u64,
+    pub rng: Box<dyn RandomSource>,
+    pub debugger: Debugger,
+    pub recorder: Option<WavRecorder>,
+    pub tracer: Option<Tracer>,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         Self {
             reg: [0; 16],
-            ram: [0; 4096],
+            ram: [0; memory::RAM_SIZE],
             ireg: 0,
             pc: memory::ADDR_INIT,
             stack: [0; 16],
@@ -30,49 +68,197 @@ impl Chip8 {
             dt: 0,
             st: 0,
             keys: [false; 16],
-            display: [false; 64 * 32],
+            display: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            plane: 1,
+            pattern: [0; 16],
+            pitch: 64,
+            hires: false,
+            rpl: [0; 16],
+            halted: false,
             opcode: 0,
+            rom_hash: [0; 20],
+            rom_size: 0,
+            quirks,
+            vblank: false,
+            ips: DEFAULT_IPS,
+            ips_remainder: 0,
+            frame_budget: 0,
+            cycles: 0,
+            rng: Box::new(SplitMix64::from_entropy()),
+            debugger: Debugger::default(),
+            recorder: None,
+            tracer: None,
         }
     }
 
-    fn push(&mut self, val: u16) {
-        if self.sp == 16 {
-            panic!("Cannot push full stack.");
+    fn push(&mut self, val: u16) -> Result<(), Chip8Error> {
+        if self.sp as usize == self.stack.len() {
+            return Err(Chip8Error::StackOverflow {
+                pc: self.pc.wrapping_sub(2),
+            });
         }
 
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, Chip8Error> {
         if self.sp == 0 {
-            panic!("Cannot pop from empty stack.");
+            return Err(Chip8Error::StackUnderflow {
+                pc: self.pc.wrapping_sub(2),
+            });
         }
 
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
+    }
+
+    // All CPU accesses to ram go through read and write so the debugger can watch them
+    fn read(&mut self, addr: usize) -> Result<u8, Chip8Error> {
+        self.debugger.check_access(addr, Access::Read);
+        self.ram
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })
+    }
+
+    fn write(&mut self, addr: usize, val: u8) -> Result<(), Chip8Error> {
+        self.debugger.check_access(addr, Access::Write);
+        let byte = self
+            .ram
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        *byte = val;
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, filename: &str) {
-        memory::load_rom(&mut self.ram, filename);
+    // Only the low nibble selects a key, as with FX29/FX30
+    fn key(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+
+    pub fn load_rom(&mut self, filename: &str) -> Result<(), Chip8Error> {
+        let contents = fs::read(filename)?;
+        self.load_rom_bytes(&contents)
+    }
+
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        memory::load_rom(&mut self.ram, rom)?;
+        self.rom_size = rom.len();
+        self.rom_hash = Sha1::from(rom).digest().bytes();
+        Ok(())
     }
 
     pub fn load_fontset(&mut self) {
-        memory::load_fontset(&mut self.ram, &FONTSET);
+        memory::load_fontset(&mut self.ram, &FONTSET, FONTSET_ADDR_INIT);
+        memory::load_fontset(&mut self.ram, &BIG_FONTSET, BIG_FONTSET_ADDR_INIT);
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            DISPLAY_WIDTH
+        } else {
+            DISPLAY_WIDTH / 2
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            DISPLAY_HEIGHT
+        } else {
+            DISPLAY_HEIGHT / 2
+        }
     }
 
-    pub fn cycle(&mut self) {
-        self.opcode =
-            (self.ram[self.pc as usize] as u16) << 8 | self.ram[self.pc as usize + 1] as u16;
-        self.pc += 2;
+    // The display buffer is always DISPLAY_WIDTH * DISPLAY_HEIGHT, but only the first
+    // width() * height() pixels are in use, laid out with a stride of width().
+    // Each pixel holds one bit per XO-CHIP bitplane; only the selected planes are touched.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let old = self.display;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let inside = sx >= 0 && sx < width && sy >= 0 && sy < height;
+                let px = if inside {
+                    old[(sy * width + sx) as usize] & self.plane
+                } else {
+                    0
+                };
+                let pindex = (y * width + x) as usize;
+                self.display[pindex] = (self.display[pindex] & !self.plane) | px;
+            }
+        }
+    }
 
-        // println!("Executing opcode: {:#04x}", self.opcode);
+    fn clear(&mut self) {
+        for px in self.display.iter_mut() {
+            *px &= !self.plane;
+        }
+    }
+
+    // Skips the next instruction, which is 4 bytes long if it is the XO-CHIP F000 NNNN.
+    // The peek isn't a fetch, so it bypasses read() and its watchpoints.
+    fn skip(&mut self) {
+        let pc = self.pc as usize;
+        let long = self.ram.get(pc..pc + 2) == Some(&[0xF0, 0x00]);
+
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    // With display_wait, a DXYN at the PC can't run until the next vblank. The check
+    // peeks RAM so a waiting DXYN isn't fetched, traced or counted again and again.
+    fn waiting_for_vblank(&self) -> bool {
+        self.quirks.display_wait
+            && !self.vblank
+            && self
+                .ram
+                .get(self.pc as usize)
+                .is_some_and(|&byte| byte >> 4 == 0xD)
+    }
 
-        if let Some(decoded) = self.decode_opcode(self.opcode) {
-            // println!("Executing opcode (decoded): {:#?}", decoded);
-            self.execute(decoded);
+    pub fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.halted || self.waiting_for_vblank() {
+            return Ok(());
+        }
+
+        let pc = self.pc as usize;
+        self.opcode = (self.read(pc)? as u16) << 8 | self.read(pc + 1)? as u16;
+        self.pc = self.pc.wrapping_add(2);
+        self.cycles += 1;
+
+        // F000 is followed by its 16-bit address
+        let next = if self.opcode == 0xF000 {
+            let pc = self.pc as usize;
+            (self.read(pc)? as u16) << 8 | self.read(pc + 1)? as u16
+        } else {
+            0
+        };
+
+        if let Some(mut tracer) = self.tracer.take() {
+            if tracer.traces(pc as u16) {
+                tracer.log(trace::format_line(self, pc as u16));
+            }
+            self.tracer = Some(tracer);
+        }
+
+        if let Some(decoded) = opcodes::decode(self.opcode, next) {
+            self.execute(decoded)?;
         } else {
-            panic!("Unknown opcode: {:#04x}", self.opcode);
+            return Err(Chip8Error::UnknownOpcode {
+                pc: pc as u16,
+                opcode: self.opcode,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn tick_timers(&mut self) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(self.st > 0);
         }
 
         if self.dt > 0 {
@@ -81,332 +267,336 @@ impl Chip8 {
         if self.st > 0 {
             self.st -= 1;
         }
+        self.vblank = true;
     }
 
-    pub fn emulate(&mut self, title: &str, delay: u64) {
-        display::emulate(title, delay, self);
-    }
+    // Runs the rest of the current 1/60 s frame of emulated time. A new frame ticks the
+    // timers and gets a budget of ips / 60 instructions, carrying the remainder over so
+    // that exactly `ips` run per second. If the debugger stops mid-frame, the next call
+    // picks up where it left off without ticking the timers again.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        if self.frame_budget == 0 {
+            if self.debugger.paused {
+                return Ok(());
+            }
 
-    fn decode_opcode(&self, opcode: u16) -> Option<Opcode> {
-        match opcode & 0xF000 {
-            0x0000 => match opcode {
-                0x00E0 => Some(Opcode::OP00E0),
-                0x00EE => Some(Opcode::OP00EE),
-                _ => None,
-            },
-            0x1000 => Some(Opcode::OP1NNN),
-            0x2000 => Some(Opcode::OP2NNN),
-            0x3000 => Some(Opcode::OP3XKK),
-            0x4000 => Some(Opcode::OP4XKK),
-            0x5000 => Some(Opcode::OP5XY0),
-            0x6000 => Some(Opcode::OP6XKK),
-            0x7000 => Some(Opcode::OP7XKK),
-            0x8000 => match opcode & 0x000F {
-                0x0 => Some(Opcode::OP8XY0),
-                0x1 => Some(Opcode::OP8XY1),
-                0x2 => Some(Opcode::OP8XY2),
-                0x3 => Some(Opcode::OP8XY3),
-                0x4 => Some(Opcode::OP8XY4),
-                0x5 => Some(Opcode::OP8XY5),
-                0x6 => Some(Opcode::OP8XY6),
-                0x7 => Some(Opcode::OP8XY7),
-                0xE => Some(Opcode::OP8XYE),
-                _ => None,
-            },
-            0x9000 => Some(Opcode::OP9XY0),
-            0xA000 => Some(Opcode::OPANNN),
-            0xB000 => Some(Opcode::OPBNNN),
-            0xC000 => Some(Opcode::OPCXKK),
-            0xD000 => Some(Opcode::OPDXYN),
-            0xE000 => match opcode & 0x00FF {
-                0x9E => Some(Opcode::OPEX9E),
-                0xA1 => Some(Opcode::OPEXA1),
-                _ => None,
-            },
-            0xF000 => match opcode & 0x00FF {
-                0x07 => Some(Opcode::OPFX07),
-                0x0A => Some(Opcode::OPFX0A),
-                0x15 => Some(Opcode::OPFX15),
-                0x18 => Some(Opcode::OPFX18),
-                0x1E => Some(Opcode::OPFX1E),
-                0x29 => Some(Opcode::OPFX29),
-                0x33 => Some(Opcode::OPFX33),
-                0x55 => Some(Opcode::OPFX55),
-                0x65 => Some(Opcode::OPFX65),
-                _ => None,
-            },
-            _ => None,
+            self.tick_timers();
+
+            let total = self.ips + self.ips_remainder;
+            self.ips_remainder = total % TIMER_HZ;
+            self.frame_budget = total / TIMER_HZ;
+        }
+
+        while self.frame_budget > 0 {
+            if self.halted {
+                break;
+            }
+            // A DXYN waiting for vblank ends the frame
+            if self.waiting_for_vblank() {
+                self.frame_budget = 0;
+                break;
+            }
+            if self.debugger.should_break(self.pc, self.sp) {
+                if let Some(tracer) = &mut self.tracer {
+                    if let Some(hit) = self.debugger.watch_hit {
+                        tracer.dump(format_args!(
+                            "watchpoint {:?} at {:03X}",
+                            hit.access, hit.addr
+                        ));
+                    } else if self.debugger.breakpoints.contains(&self.pc) {
+                        tracer.dump(format_args!("breakpoint at {:03X}", self.pc));
+                    }
+                }
+                break;
+            }
+            if let Err(err) = self.cycle() {
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.dump(&err);
+                }
+                return Err(err);
+            }
+            self.frame_budget -= 1;
         }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sdl")]
+    pub fn emulate(
+        &mut self,
+        title: &str,
+        rom_path: &str,
+        options: &display::Options,
+    ) -> Result<(), Chip8Error> {
+        display::emulate(title, Path::new(rom_path), self, options)
     }
 
     // https://austinmorlan.com/posts/chip8_emulator/#the-instructions
-    fn execute(&mut self, opcode: Opcode) {
-        match opcode {
-            Opcode::OP00E0 => self.display = [false; 64 * 32],
-            Opcode::OP00EE => {
-                // println!("Stack Pointer: {}, Stack: {:?}", self.sp, self.stack);
-                let nnn = self.pop();
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        match instruction {
+            Instruction::Cls => self.clear(),
+            Instruction::Ret => {
+                let nnn = self.pop()?;
 
                 self.pc = nnn;
             }
-            Opcode::OP1NNN => {
-                // println!("Stack Pointer: {}, Stack: {:?}", self.sp, self.stack);
-                let nnn = self.opcode & 0xFFF;
-
+            Instruction::ScrollDown { n } => self.scroll(0, n as isize),
+            Instruction::ScrollUp { n } => self.scroll(0, -(n as isize)),
+            Instruction::ScrollRight => self.scroll(4, 0),
+            Instruction::ScrollLeft => self.scroll(-4, 0),
+            Instruction::Exit => self.halted = true,
+            Instruction::Low => {
+                self.hires = false;
+                self.display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+            }
+            Instruction::High => {
+                self.hires = true;
+                self.display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+            }
+            Instruction::Jump { nnn } => {
                 self.pc = nnn;
             }
-            Opcode::OP2NNN => {
-                let nnn = self.opcode & 0xFFF;
-
-                self.push(self.pc);
+            Instruction::Call { nnn } => {
+                self.push(self.pc)?;
                 self.pc = nnn;
             }
-            Opcode::OP3XKK => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                /*
-                Keep bits beetween the 8-11 which rappresent the register, just then push the obtained register by 8 bits
-
-                opcode:  1010 0010 0011 0100  (0xA234)
-                mask:    0000 1111 0000 0000  (0x0F00)
-                -----------------------------
-                result:  0000 0010 0000 0000  (0x0200) ==> 0x0200 >> 8 = 0x02 or 0000 0000 0000 0020
-                */
-
-                let kk = self.opcode & 0xFF;
-
-                if self.reg[vx as usize] == kk as u8 {
-                    self.pc += 2;
+            Instruction::SkipEqImm { x, kk } => {
+                if self.reg[x] == kk {
+                    self.skip();
                 }
             }
-            Opcode::OP4XKK => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let kk = self.opcode & 0xFF;
-
-                if self.reg[vx as usize] != kk as u8 {
-                    self.pc += 2;
+            Instruction::SkipNeImm { x, kk } => {
+                if self.reg[x] != kk {
+                    self.skip();
                 }
             }
-            Opcode::OP5XY0 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                if self.reg[vx as usize] == self.reg[vy as usize] {
-                    self.pc += 2;
+            Instruction::SkipEq { x, y } => {
+                if self.reg[x] == self.reg[y] {
+                    self.skip();
                 }
             }
-            Opcode::OP6XKK => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let kk = self.opcode & 0xFF;
-
-                self.reg[vx as usize] = kk as u8;
-            }
-            Opcode::OP7XKK => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let kk = self.opcode & 0xFF;
+            Instruction::SaveRange { x, y } => {
+                let (vx, vy) = (x.0 as usize, y.0 as usize);
 
-                // println!("OP7XKK vx reg: {}", self.reg[vx as usize]); // 43
-                // println!("OP7XKK kk reg: {}", kk); // 255
-                self.reg[vx as usize] = self.reg[vx as usize].wrapping_add(kk as u8)
+                for i in 0..=vx.abs_diff(vy) {
+                    let r = if vx <= vy { vx + i } else { vx - i };
+                    self.write(self.ireg as usize + i, self.reg[r])?;
+                }
             }
-            Opcode::OP8XY0 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
+            Instruction::LoadRange { x, y } => {
+                let (vx, vy) = (x.0 as usize, y.0 as usize);
 
-                self.reg[vx as usize] = self.reg[vy as usize];
+                for i in 0..=vx.abs_diff(vy) {
+                    let r = if vx <= vy { vx + i } else { vx - i };
+                    self.reg[r] = self.read(self.ireg as usize + i)?;
+                }
             }
-            Opcode::OP8XY1 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                self.reg[vx as usize] |= self.reg[vy as usize];
+            Instruction::LoadImm { x, kk } => self.reg[x] = kk,
+            Instruction::AddImm { x, kk } => self.reg[x] = self.reg[x].wrapping_add(kk),
+            Instruction::Load { x, y } => self.reg[x] = self.reg[y],
+            Instruction::Or { x, y } => {
+                self.reg[x] |= self.reg[y];
+                if self.quirks.vf_reset {
+                    self.reg[0xF] = 0;
+                }
             }
-            Opcode::OP8XY2 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                self.reg[vx as usize] &= self.reg[vy as usize];
+            Instruction::And { x, y } => {
+                self.reg[x] &= self.reg[y];
+                if self.quirks.vf_reset {
+                    self.reg[0xF] = 0;
+                }
             }
-            Opcode::OP8XY3 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                self.reg[vx as usize] ^= self.reg[vy as usize];
+            Instruction::Xor { x, y } => {
+                self.reg[x] ^= self.reg[y];
+                if self.quirks.vf_reset {
+                    self.reg[0xF] = 0;
+                }
             }
-            Opcode::OP8XY4 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
+            Instruction::Add { x, y } => {
+                let (newvx, carry) = self.reg[x].overflowing_add(self.reg[y]);
 
-                let (newvx, carry) = self.reg[vx as usize].overflowing_add(self.reg[vy as usize]);
-                self.reg[0xF] = if carry { 1 } else { 0 };
-
-                self.ram[vx as usize] = newvx;
+                // The flag is written last so that it wins when x is VF
+                self.reg[x] = newvx;
+                self.reg[0xF] = carry as u8;
             }
-            Opcode::OP8XY5 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                let (newvx, borrow) = self.reg[vx as usize].overflowing_sub(self.reg[vy as usize]);
-                self.reg[0xF] = if borrow { 0 } else { 1 };
+            Instruction::Sub { x, y } => {
+                let (newvx, borrow) = self.reg[x].overflowing_sub(self.reg[y]);
 
-                // println!("OP8XY5 vx: {}", self.reg[vx as usize]);
-                // println!("OP8XY5 vy: {}", self.reg[vy as usize]);
-                self.ram[vx as usize] = newvx;
+                self.reg[x] = newvx;
+                self.reg[0xF] = !borrow as u8;
             }
-            Opcode::OP8XY6 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.reg[0xF] = self.reg[vx as usize] & 0x1;
+            Instruction::ShiftRight { x, y } => {
+                let val = if self.quirks.shift_vy {
+                    self.reg[y]
+                } else {
+                    self.reg[x]
+                };
 
-                self.reg[vx as usize] >>= 1;
+                self.reg[x] = val >> 1;
+                self.reg[0xF] = val & 0x1;
             }
-            Opcode::OP8XY7 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
+            Instruction::SubN { x, y } => {
+                let (newvx, borrow) = self.reg[y].overflowing_sub(self.reg[x]);
 
-                let (newvx, borrow) = self.reg[vy as usize].overflowing_sub(self.reg[vx as usize]);
-                self.reg[0xF] = if borrow { 0 } else { 1 };
-
-                self.ram[vx as usize] = newvx;
+                self.reg[x] = newvx;
+                self.reg[0xF] = !borrow as u8;
             }
-            Opcode::OP8XYE => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.reg[0xF] = (self.reg[vx as usize] & 0x80) >> 7;
+            Instruction::ShiftLeft { x, y } => {
+                let val = if self.quirks.shift_vy {
+                    self.reg[y]
+                } else {
+                    self.reg[x]
+                };
 
-                self.reg[vx as usize] <<= 1;
+                self.reg[x] = val << 1;
+                self.reg[0xF] = (val & 0x80) >> 7;
             }
-            Opcode::OP9XY0 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let vy = (self.opcode & 0xF0) >> 4;
-
-                if self.reg[vx as usize] != self.reg[vy as usize] {
-                    self.pc += 2;
+            Instruction::SkipNe { x, y } => {
+                if self.reg[x] != self.reg[y] {
+                    self.skip();
                 }
             }
-            Opcode::OPANNN => {
-                let nnn = self.opcode & 0xFFF;
+            Instruction::LoadI { nnn } => self.ireg = nnn,
+            Instruction::JumpV0 { nnn } => {
+                let vx = if self.quirks.jump_vx { nnn >> 8 } else { 0 };
 
-                self.ireg = nnn;
+                self.pc = nnn + self.reg[vx as usize] as u16;
             }
-            Opcode::OPBNNN => {
-                let nnn = self.opcode & 0xFFF;
+            Instruction::Random { x, kk } => self.reg[x] = self.rng.next_byte() & kk,
+            Instruction::Draw { x, y, n } => {
+                let vx = self.reg[x];
+                let vy = self.reg[y];
 
-                self.pc = nnn + self.reg[0] as u16;
-            }
-            Opcode::OPCXKK => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let kk = self.opcode & 0xFF;
+                if self.quirks.display_wait {
+                    self.vblank = false;
+                }
 
-                self.reg[vx as usize] = rand_byte() & kk as u8;
-            }
-            Opcode::OPDXYN => {
-                let vx = self.reg[((self.opcode & 0x0F00) >> 8) as usize];
-                let vy = self.reg[((self.opcode & 0x00F0) >> 4) as usize];
-                let n = (self.opcode & 0x000F) as u8;
+                // DXY0 draws a 16x16 sprite made of 16-bit rows
+                let (rows, cols) = if n == 0 { (16, 16) } else { (n as usize, 8) };
+                let (width, height) = (self.width(), self.height());
 
+                // With both XO-CHIP planes selected, the second plane's sprite follows the first
+                let mut addr = self.ireg as usize;
                 self.reg[0xF] = 0;
-                for bindex in 0..n {
-                    let y = (vy.wrapping_add(bindex)) % 32;
-                    let sprite_byte = self.ram[(self.ireg + bindex as u16) as usize];
-                    for btindex in 0..8 {
-                        let x = (vx.wrapping_add(btindex)) % 64;
-                        let pindex = (y as usize) * 64 + (x as usize);
-                        let spixel = (sprite_byte >> (7 - btindex)) & 1;
-
-                        if spixel == 1 {
-                            if self.display[pindex] == true {
-                                self.reg[0xF] = 1;
+                for bit in [1, 2] {
+                    if self.plane & bit == 0 {
+                        continue;
+                    }
+
+                    for bindex in 0..rows {
+                        let y = (vy as usize % height) + bindex;
+                        if y >= height && self.quirks.clip_sprites {
+                            break;
+                        }
+                        let sprite_row = if cols == 16 {
+                            let row = addr + 2 * bindex;
+                            (self.read(row)? as u16) << 8 | self.read(row + 1)? as u16
+                        } else {
+                            (self.read(addr + bindex)? as u16) << 8
+                        };
+                        for btindex in 0..cols {
+                            let x = (vx as usize % width) + btindex;
+                            if x >= width && self.quirks.clip_sprites {
+                                break;
+                            }
+                            let pindex = (y % height) * width + (x % width);
+                            let spixel = (sprite_row >> (15 - btindex)) & 1;
+
+                            if spixel == 1 {
+                                if self.display[pindex] & bit != 0 {
+                                    self.reg[0xF] = 1;
+                                }
+                                self.display[pindex] ^= bit;
                             }
-                            self.display[pindex] ^= true;
                         }
                     }
+                    addr += rows * cols / 8;
                 }
             }
-            Opcode::OPEX9E => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                let key = self.reg[vx as usize];
-                if self.keys[key as usize] {
-                    self.pc += 2;
+            Instruction::SkipKey { x } => {
+                if self.key(self.reg[x]) {
+                    self.skip();
                 }
             }
-            Opcode::OPEXA1 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                let key = self.reg[vx as usize];
-                if !self.keys[key as usize] {
-                    self.pc += 2;
+            Instruction::SkipNotKey { x } => {
+                if !self.key(self.reg[x]) {
+                    self.skip();
                 }
             }
-            Opcode::OPFX07 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.reg[vx as usize] = self.dt;
+            Instruction::LoadILong { nnnn } => {
+                self.ireg = nnnn;
+                self.pc = self.pc.wrapping_add(2);
             }
-            Opcode::OPFX0A => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                for i in 0..self.keys.len() {
-                    if i != (self.keys.len() - 1) {
-                        if self.keys[i] {
-                            self.reg[vx as usize] = i as u8;
-                            break;
-                        }
-                    } else {
-                        self.pc -= 2;
-                    }
+            Instruction::Plane { n } => self.plane = n & 0x3,
+            Instruction::Audio => {
+                for i in 0..self.pattern.len() {
+                    self.pattern[i] = self.read(self.ireg as usize + i)?;
                 }
             }
-            Opcode::OPFX15 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.dt = self.reg[vx as usize];
-            }
-            Opcode::OPFX18 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.st = self.reg[vx as usize];
-            }
-            Opcode::OPFX1E => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.ireg += self.reg[vx as usize] as u16;
+            Instruction::LoadDelay { x } => self.reg[x] = self.dt,
+            Instruction::WaitKey { x } => match self.keys.iter().position(|&k| k) {
+                Some(key) => self.reg[x] = key as u8,
+                None => self.pc = self.pc.wrapping_sub(2),
+            },
+            Instruction::SetDelay { x } => self.dt = self.reg[x],
+            Instruction::SetSound { x } => self.st = self.reg[x],
+            Instruction::AddI { x } => self.ireg = self.ireg.wrapping_add(self.reg[x] as u16),
+            Instruction::LoadFont { x } => {
+                self.ireg = FONTSET_ADDR_INIT as u16 + 5 * (self.reg[x] & 0xF) as u16;
             }
-            Opcode::OPFX29 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
-                self.ireg = (FONTSET_ADDR_INIT + (5 * self.reg[vx as usize])) as u16;
+            Instruction::LoadBigFont { x } => {
+                self.ireg = BIG_FONTSET_ADDR_INIT as u16 + 10 * (self.reg[x] & 0xF) as u16;
             }
-            Opcode::OPFX33 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-                let mut val = self.reg[vx as usize];
+            Instruction::Bcd { x } => {
+                let mut val = self.reg[x];
 
-                self.ram[self.ireg as usize + 2] = val % 10;
+                self.write(self.ireg as usize + 2, val % 10)?;
                 val /= 10;
 
-                self.ram[self.ireg as usize + 1] = val % 10;
+                self.write(self.ireg as usize + 1, val % 10)?;
                 val /= 10;
 
-                self.ram[self.ireg as usize] = val % 10;
+                self.write(self.ireg as usize, val % 10)?;
             }
-            Opcode::OPFX55 => {
-                let vx = (self.opcode & 0xF00) >> 8;
-
+            Instruction::Pitch { x } => self.pitch = self.reg[x],
+            Instruction::Store { x } => {
                 let mut i = 0;
-                while i <= vx as usize {
-                    self.ram[self.ireg as usize + i] = self.reg[i];
+                while i <= x.0 as usize {
+                    self.write(self.ireg as usize + i, self.reg[i])?;
                     i += 1;
                 }
-            }
-            Opcode::OPFX65 => {
-                let vx = (self.opcode & 0xF00) >> 8;
 
+                if self.quirks.load_store_inc {
+                    self.ireg = self.ireg.wrapping_add(x.0 as u16 + 1);
+                } else if self.quirks.load_store_inc_x {
+                    self.ireg = self.ireg.wrapping_add(x.0 as u16);
+                }
+            }
+            Instruction::Restore { x } => {
                 let mut i = 0;
-                while i <= vx as usize {
-                    self.reg[i] = self.ram[self.ireg as usize + i];
+                while i <= x.0 as usize {
+                    self.reg[i] = self.read(self.ireg as usize + i)?;
                     i += 1;
                 }
+
+                if self.quirks.load_store_inc {
+                    self.ireg = self.ireg.wrapping_add(x.0 as u16 + 1);
+                } else if self.quirks.load_store_inc_x {
+                    self.ireg = self.ireg.wrapping_add(x.0 as u16);
+                }
+            }
+            Instruction::SaveFlags { x } => {
+                let n = x.0 as usize;
+
+                self.rpl[..=n].copy_from_slice(&self.reg[..=n]);
+            }
+            Instruction::LoadFlags { x } => {
+                let n = x.0 as usize;
+
+                self.reg[..=n].copy_from_slice(&self.rpl[..=n]);
             }
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+
+use super::cpu::Chip8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Mode {
+    #[default]
+    Run,
+    Step,
+    // Stop at addr; with a stack depth, only once the stack is back at that depth
+    RunTo {
+        addr: u16,
+        sp: Option<u8>,
+    },
+    // Stop once the stack is shallower than sp, i.e. after the matching 00EE
+    StepOut {
+        sp: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Watches the inclusive address range start..=end for reads, writes or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, addr: usize, access: Access) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        watched && (self.start as usize..=self.end as usize).contains(&addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: usize,
+    pub access: Access,
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    pub paused: bool,
+    pub breakpoints: BTreeSet<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    // The first watched access since execution last resumed
+    pub watch_hit: Option<WatchHit>,
+    mode: Mode,
+    // Set when execution resumes so that the instruction under the PC runs even if it
+    // is the breakpoint we just stopped at
+    skip: bool,
+}
+
+impl Debugger {
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.mode = Mode::Run;
+    }
+
+    pub fn resume(&mut self) {
+        self.continue_with(Mode::Run);
+    }
+
+    pub fn step(&mut self) {
+        self.continue_with(Mode::Step);
+    }
+
+    pub fn run_to(&mut self, addr: u16) {
+        self.continue_with(Mode::RunTo { addr, sp: None });
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: u16) {
+        if !self.breakpoints.remove(&addr) {
+            self.breakpoints.insert(addr);
+        }
+    }
+
+    pub fn toggle_watchpoint(&mut self, watchpoint: Watchpoint) {
+        match self.watchpoints.iter().position(|&w| w == watchpoint) {
+            Some(i) => {
+                self.watchpoints.remove(i);
+            }
+            None => self.watchpoints.push(watchpoint),
+        }
+    }
+
+    fn continue_with(&mut self, mode: Mode) {
+        self.paused = false;
+        self.skip = true;
+        self.mode = mode;
+        self.watch_hit = None;
+    }
+
+    // Called on every CPU memory access. A hit lets the current instruction finish and
+    // pauses before the next one.
+    pub(crate) fn check_access(&mut self, addr: usize, access: Access) {
+        if self.watchpoints.iter().any(|w| w.matches(addr, access)) {
+            if self.watch_hit.is_none() {
+                self.watch_hit = Some(WatchHit { addr, access });
+            }
+            self.pause();
+        }
+    }
+
+    // Called before each instruction; returns true (and pauses) if execution should stop
+    pub fn should_break(&mut self, pc: u16, sp: u8) -> bool {
+        if self.paused {
+            return true;
+        }
+        if std::mem::take(&mut self.skip) {
+            return false;
+        }
+
+        let stop = self.breakpoints.contains(&pc)
+            || match self.mode {
+                Mode::Run => false,
+                Mode::Step => true,
+                Mode::RunTo { addr, sp: depth } => pc == addr && depth.is_none_or(|d| sp == d),
+                Mode::StepOut { sp: depth } => sp < depth,
+            };
+
+        if stop {
+            self.pause();
+        }
+        stop
+    }
+}
+
+impl Chip8 {
+    // Steps over 2NNN calls by running until the instruction after the call is reached
+    // again at the same stack depth; any other instruction is a single step.
+    pub fn step_over(&mut self) {
+        let pc = self.pc as usize;
+        let is_call = self.ram.get(pc).is_some_and(|&hi| hi & 0xF0 == 0x20);
+
+        if is_call {
+            let addr = self.pc.wrapping_add(2);
+            self.debugger.continue_with(Mode::RunTo {
+                addr,
+                sp: Some(self.sp),
+            });
+        } else {
+            self.debugger.step();
+        }
+    }
+
+    pub fn step_out(&mut self) {
+        if self.sp == 0 {
+            self.debugger.resume();
+        } else {
+            self.debugger.continue_with(Mode::StepOut { sp: self.sp });
+        }
+    }
+}
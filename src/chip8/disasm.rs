@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::memory::ADDR_INIT;
+use super::opcodes::{self, Instruction};
+
+const DATA_PER_LINE: usize = 8;
+
+fn word(ram: &[u8], addr: usize) -> u16 {
+    let hi = ram.get(addr).copied().unwrap_or(0);
+    let lo = ram.get(addr + 1).copied().unwrap_or(0);
+    (hi as u16) << 8 | lo as u16
+}
+
+fn decode_at(ram: &[u8], addr: usize) -> Option<Instruction> {
+    opcodes::decode(word(ram, addr), word(ram, addr + 2))
+}
+
+fn flush_data(out: &mut String, data: &mut Vec<u8>, addr: usize) {
+    if data.is_empty() {
+        return;
+    }
+
+    let bytes: Vec<String> = data.iter().map(|b| format!("0x{:02X}", b)).collect();
+    writeln!(out, "0x{:03X}: db {}", addr, bytes.join(", ")).unwrap();
+    data.clear();
+}
+
+fn label(addr: u16) -> String {
+    format!("L{:03X}", addr)
+}
+
+fn addr(addr: u16, labels: &BTreeSet<u16>) -> String {
+    if labels.contains(&addr) {
+        label(addr)
+    } else {
+        format!("0x{:03X}", addr)
+    }
+}
+
+// Formats one instruction with Cowgod-style mnemonics, e.g. "DRW V1, V2, 5". Address
+// operands in `labels` are printed as label names.
+pub fn format(instruction: Instruction, labels: &BTreeSet<u16>) -> String {
+    match instruction {
+        Instruction::Jump { nnn } => format!("JP {}", addr(nnn, labels)),
+        Instruction::Call { nnn } => format!("CALL {}", addr(nnn, labels)),
+        Instruction::LoadI { nnn } => format!("LD I, {}", addr(nnn, labels)),
+        Instruction::JumpV0 { nnn } => format!("JP V0, {}", addr(nnn, labels)),
+        _ => instruction.to_string(),
+    }
+}
+
+// Text for whatever is at addr: the instruction if the word decodes, otherwise its
+// two bytes as data. Used by the debugger, which has no reachability information.
+pub fn disassemble_at(ram: &[u8], addr: u16) -> String {
+    match decode_at(ram, addr as usize) {
+        Some(instruction) => instruction.to_string(),
+        None => {
+            let opcode = word(ram, addr as usize);
+            format!("db 0x{:02X}, 0x{:02X}", opcode >> 8, opcode & 0xFF)
+        }
+    }
+}
+
+// Follows every path from entry (both sides of skips, calls and their return
+// address) and returns the addresses of reachable instructions plus the jump and call
+// targets among them. BNNN's target depends on V0, so it is not followed.
+fn trace(ram: &[u8], start: usize, end: usize, entry: usize) -> (BTreeSet<usize>, BTreeSet<u16>) {
+    let mut code = BTreeSet::new();
+    let mut targets = BTreeSet::new();
+    let mut pending = vec![entry];
+
+    while let Some(pc) = pending.pop() {
+        if pc < start || pc + 2 > end || code.contains(&pc) {
+            continue;
+        }
+
+        let Some(instruction) = decode_at(ram, pc) else {
+            continue;
+        };
+        let next = pc + instruction.size();
+        if next > end {
+            continue;
+        }
+        code.insert(pc);
+
+        match instruction {
+            Instruction::Ret | Instruction::Exit | Instruction::JumpV0 { .. } => {}
+            Instruction::Jump { nnn } => {
+                targets.insert(nnn);
+                pending.push(nnn as usize);
+            }
+            Instruction::Call { nnn } => {
+                targets.insert(nnn);
+                pending.push(nnn as usize);
+                pending.push(next);
+            }
+            Instruction::SkipEqImm { .. }
+            | Instruction::SkipNeImm { .. }
+            | Instruction::SkipEq { .. }
+            | Instruction::SkipNe { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. } => {
+                pending.push(next);
+                pending.push(next + decode_at(ram, next).map_or(2, Instruction::size));
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    (code, targets)
+}
+
+// Disassembles ram[start..end] starting execution at entry. Reachable instructions are
+// printed as "0x21A: DRW V1, V2, 5", jump/call targets get an "L21A:" label line, and
+// everything else is printed as "db" data.
+pub fn disassemble(ram: &[u8], start: usize, end: usize, entry: usize) -> String {
+    let end = end.min(ram.len());
+    let (code, targets) = trace(ram, start, end, entry);
+
+    // Only label targets that start a line of output; a jump into the middle of another
+    // instruction keeps its numeric address
+    let mut starts = BTreeSet::new();
+    let mut pos = start;
+    while pos < end {
+        if code.contains(&pos) {
+            starts.insert(pos as u16);
+            pos += decode_at(ram, pos).unwrap().size();
+        } else {
+            pos += 1;
+        }
+    }
+    let labels: BTreeSet<u16> = targets.into_iter().filter(|t| starts.contains(t)).collect();
+
+    let mut out = String::new();
+    let mut data: Vec<u8> = Vec::new();
+    let mut data_addr = start;
+
+    let mut pos = start;
+    while pos < end {
+        let is_code = code.contains(&pos);
+        if labels.contains(&(pos as u16)) || is_code || data.len() == DATA_PER_LINE {
+            flush_data(&mut out, &mut data, data_addr);
+        }
+        if labels.contains(&(pos as u16)) {
+            writeln!(out, "{}:", label(pos as u16)).unwrap();
+        }
+
+        if is_code {
+            let instruction = decode_at(ram, pos).unwrap();
+            writeln!(out, "0x{:03X}: {}", pos, format(instruction, &labels)).unwrap();
+            pos += instruction.size();
+        } else {
+            if data.is_empty() {
+                data_addr = pos;
+            }
+            data.push(ram[pos]);
+            pos += 1;
+        }
+    }
+    flush_data(&mut out, &mut data, data_addr);
+
+    out
+}
+
+pub fn disassemble_rom(rom: &[u8]) -> String {
+    let mut ram = vec![0; ADDR_INIT as usize + rom.len()];
+    ram[ADDR_INIT as usize..].copy_from_slice(rom);
+
+    let start = ADDR_INIT as usize;
+    disassemble(&ram, start, ram.len(), start)
+}
@@ -1,19 +1,106 @@
 extern crate sdl2;
 
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::controller::{Axis, Button, GameController};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-use super::cpu::Chip8;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-const SCALE: u32 = 10;
-const HEIGHT: u32 = 32 * SCALE;
-const WIDTH: u32 = 64 * SCALE;
+use super::audio::{Tone, ToneGenerator};
+use super::config;
+use super::cpu::{Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH, TIMER_HZ};
+use super::debugger::{Access, Watchpoint};
+use super::disasm;
+use super::error::Chip8Error;
+use super::frontend::{self, Frontend};
+use super::keymap::KeyMap;
+use super::memory::ADDR_INIT;
+use super::palette::Palette;
 
-fn debug(chip8: &Chip8, canvas: &mut Canvas<Window>, ttf_context: &sdl2::ttf::Sdl2TtfContext) {
+pub const DEFAULT_DEAD_ZONE: u16 = 8000;
+
+pub struct Options {
+    // Window pixels per lo-res CHIP-8 pixel
+    pub scale: u32,
+    pub palette: Palette,
+    // Open the debugger window next to the game
+    pub debugger: bool,
+    pub keys: KeyMap,
+    pub buttons: KeyMap,
+    // Analog stick deflection (out of 32767) needed to press a D-pad direction
+    pub dead_zone: u16,
+    pub tone: Tone,
+    // Where the rebinding screen saves the new keys
+    pub config_path: Option<PathBuf>,
+}
+
+fn scancodes(keymap: &KeyMap) -> HashMap<Scancode, u8> {
+    let mut scancodes = HashMap::new();
+
+    for (k, names) in keymap.0.iter().enumerate() {
+        for name in names {
+            match Scancode::from_name(name) {
+                Some(scancode) => {
+                    scancodes.insert(scancode, k as u8);
+                }
+                None => eprintln!("Unknown key name `{}` for CHIP-8 key {:X}", name, k),
+            }
+        }
+    }
+
+    scancodes
+}
+
+fn buttons(keymap: &KeyMap) -> HashMap<Button, u8> {
+    let mut buttons = HashMap::new();
+
+    for (k, names) in keymap.0.iter().enumerate() {
+        for name in names {
+            match Button::from_string(name) {
+                Some(button) => {
+                    buttons.insert(button, k as u8);
+                }
+                None => eprintln!("Unknown button name `{}` for CHIP-8 key {:X}", name, k),
+            }
+        }
+    }
+
+    buttons
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scale: 10,
+            palette: Palette::default(),
+            debugger: true,
+            keys: KeyMap::default(),
+            buttons: KeyMap::gamepad(),
+            dead_zone: DEFAULT_DEAD_ZONE,
+            tone: Tone::default(),
+            config_path: None,
+        }
+    }
+}
+
+const DEBUG_WIDTH: u32 = 680;
+const DEBUG_HEIGHT: u32 = 500;
+const MEMORY_LINES: u16 = 16;
+
+fn debug(
+    chip8: &Chip8,
+    error: Option<&str>,
+    cursor: u16,
+    canvas: &mut Canvas<Window>,
+    ttf_context: &sdl2::ttf::Sdl2TtfContext,
+) {
     let font = ttf_context
         .load_font("src/assets/consolas.ttf", 16)
         .unwrap();
@@ -40,6 +127,75 @@ fn debug(chip8: &Chip8, canvas: &mut Canvas<Window>, ttf_context: &sdl2::ttf::Sd
         Color::RGB(255, 255, 255),
     );
     draw_text(canvas, &font, "Stack:", 180, 40, Color::RGB(255, 255, 255));
+    draw_text(
+        canvas,
+        &font,
+        "Disassembly:",
+        370,
+        40,
+        Color::RGB(255, 255, 255),
+    );
+
+    let status_text = if let Some(hit) = chip8.debugger.watch_hit {
+        let access = match hit.access {
+            Access::Read => "read",
+            Access::Write => "write",
+        };
+        format!("Paused at {:03X} ({} {:03X})", chip8.pc, access, hit.addr)
+    } else if chip8.debugger.paused {
+        format!("Paused at {:03X}", chip8.pc)
+    } else {
+        "Running".to_string()
+    };
+    draw_text(
+        canvas,
+        &font,
+        &status_text,
+        180,
+        10,
+        Color::RGB(255, 255, 255),
+    );
+
+    // "> 21A: LD VA, 0x02" marks the PC, "*" a breakpoint, "w" a watched word; the cursor
+    // line is highlighted
+    let start = cursor.saturating_sub(MEMORY_LINES / 2 * 2);
+    for line in 0..MEMORY_LINES {
+        let addr = start.wrapping_add(line * 2);
+        let memory_text = format!(
+            "{}{}{} {:03X}: {}",
+            if addr == chip8.pc { ">" } else { " " },
+            if chip8.debugger.breakpoints.contains(&addr) {
+                "*"
+            } else {
+                " "
+            },
+            if chip8
+                .debugger
+                .watchpoints
+                .iter()
+                .any(|w| w.start <= addr.wrapping_add(1) && addr <= w.end)
+            {
+                "w"
+            } else {
+                " "
+            },
+            addr,
+            disasm::disassemble_at(&chip8.ram, addr),
+        );
+        let color = if addr == cursor {
+            Color::RGB(255, 220, 100)
+        } else {
+            Color::RGB(200, 200, 200)
+        };
+        draw_text(
+            canvas,
+            &font,
+            &memory_text,
+            370,
+            60 + line as i32 * 20,
+            color,
+        );
+    }
 
     let mut y = 60;
     for (i, &value) in chip8.reg.iter().enumerate() {
@@ -67,6 +223,35 @@ fn debug(chip8: &Chip8, canvas: &mut Canvas<Window>, ttf_context: &sdl2::ttf::Sd
     let ireg_text = format!("I Register: {:04X}", chip8.ireg);
     draw_text(canvas, &font, &ireg_text, 10, y, Color::RGB(255, 255, 255));
 
+    if let Some(err) = error {
+        draw_text(canvas, &font, err, 10, y + 30, Color::RGB(255, 80, 80));
+    }
+
+    draw_text(
+        canvas,
+        &font,
+        "Space: run/pause  S: step  O: step over  U: step out",
+        10,
+        y + 60,
+        Color::RGB(150, 150, 150),
+    );
+    draw_text(
+        canvas,
+        &font,
+        "Up/Down: move  B: breakpoint  G: run to  Home: PC",
+        10,
+        y + 80,
+        Color::RGB(150, 150, 150),
+    );
+    draw_text(
+        canvas,
+        &font,
+        "W/R: watch writes/reads  C: watch writes to the ROM",
+        10,
+        y + 100,
+        Color::RGB(150, 150, 150),
+    );
+
     canvas.present();
 }
 
@@ -87,108 +272,519 @@ fn draw_text(
     canvas.copy(&texture, None, Some(target)).unwrap();
 }
 
-pub fn emulate(title: &str, delay: u64, chip8: &mut Chip8) {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window(title, WIDTH, HEIGHT)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
+pub struct Sdl {
+    _sdl_context: sdl2::Sdl,
+    canvas: Canvas<Window>,
+    dcanvas: Option<Canvas<Window>>,
+    ttf_context: sdl2::ttf::Sdl2TtfContext,
+    event_pump: sdl2::EventPump,
+    error: Option<String>,
+    deadline: Instant,
+    rom_path: PathBuf,
+    palette: Palette,
+    keymap: KeyMap,
+    scancodes: HashMap<Scancode, u8>,
+    // Mapped keys currently held down, so releasing one of several keys bound to the
+    // same CHIP-8 key doesn't release it
+    held: HashSet<Scancode>,
+    controller_subsystem: sdl2::GameControllerSubsystem,
+    // Open controllers by instance id; SDL reports connected and hot-plugged ones alike
+    // with ControllerDeviceAdded
+    controllers: HashMap<u32, GameController>,
+    buttons: HashMap<Button, u8>,
+    // Buttons held on each controller, and D-pad directions its left stick is pushed in
+    held_buttons: HashSet<(u32, Button)>,
+    stick: HashSet<(u32, Button)>,
+    dead_zone: i16,
+    config_path: Option<PathBuf>,
+    rebind: Option<Rebind>,
+    // None if the audio device couldn't be opened
+    audio: Option<AudioDevice<Beeper>>,
+    muted: bool,
+    rewinding: bool,
+    // Address selected in the debugger's memory list; None follows the PC
+    cursor: Option<u16>,
+}
 
-    let dwindow = video_subsystem
-        .window("Debugger", 400, 400)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
+const REWIND_SECONDS: usize = 10;
 
-    let ttf_context = sdl2::ttf::init().unwrap();
+const SAMPLE_RATE: i32 = 44100;
 
-    let mut dcanvas = dwindow.into_canvas().present_vsync().build().unwrap();
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-    canvas.clear();
-    canvas.present();
+struct Beeper(ToneGenerator);
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.render(out);
+    }
+}
+
+// Keeps running with the tone off between beeps so the generator can fade out
+fn open_audio(sdl_context: &sdl2::Sdl, tone: Tone) -> Result<AudioDevice<Beeper>, String> {
+    let spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE),
+        channels: Some(1),
+        samples: Some(512),
+    };
+
+    let device = sdl_context.audio()?.open_playback(None, &spec, |spec| {
+        Beeper(ToneGenerator::new(spec.freq as u32, tone))
+    })?;
+    device.resume();
+    Ok(device)
+}
+
+// The F10 rebinding screen asks for each CHIP-8 key in keypad order; every key pressed
+// is bound to it until Enter moves on to the next
+struct Rebind {
+    // Index into KeyMap::LAYOUT
+    index: usize,
+    keymap: KeyMap,
+    // Whether the current CHIP-8 key has had a key pressed yet
+    pressed: bool,
+    was_paused: bool,
+}
+
+// F1-F9 save to the numbered slot, Shift+F1-F9 load from it
+const SLOT_KEYS: [Keycode; 9] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9,
+];
+
+impl Sdl {
+    pub fn new(title: &str, rom_path: &Path, options: &Options) -> Self {
+        let sdl_context = sdl2::init().unwrap();
+        let video_subsystem = sdl_context.video().unwrap();
+        let window = video_subsystem
+            .window(
+                title,
+                (DISPLAY_WIDTH / 2) as u32 * options.scale,
+                (DISPLAY_HEIGHT / 2) as u32 * options.scale,
+            )
+            .position_centered()
+            .opengl()
+            .build()
+            .unwrap();
+
+        let dcanvas = options.debugger.then(|| {
+            let dwindow = video_subsystem
+                .window("Debugger", DEBUG_WIDTH, DEBUG_HEIGHT)
+                .position_centered()
+                .opengl()
+                .build()
+                .unwrap();
+            dwindow.into_canvas().build().unwrap()
+        });
+
+        let ttf_context = sdl2::ttf::init().unwrap();
+
+        let mut canvas = window.into_canvas().build().unwrap();
+        canvas.clear();
+        canvas.present();
+
+        let event_pump = sdl_context.event_pump().unwrap();
+        let controller_subsystem = sdl_context.game_controller().unwrap();
 
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut r = false;
+        let audio = open_audio(&sdl_context, options.tone)
+            .map_err(|err| eprintln!("Audio disabled: {}", err))
+            .ok();
 
-    while !r {
-        for evt in event_pump.poll_iter() {
+        Self {
+            _sdl_context: sdl_context,
+            canvas,
+            dcanvas,
+            ttf_context,
+            event_pump,
+            error: None,
+            deadline: Instant::now(),
+            rom_path: rom_path.to_path_buf(),
+            palette: options.palette,
+            keymap: options.keys.clone(),
+            scancodes: scancodes(&options.keys),
+            held: HashSet::new(),
+            controller_subsystem,
+            controllers: HashMap::new(),
+            buttons: buttons(&options.buttons),
+            held_buttons: HashSet::new(),
+            stick: HashSet::new(),
+            dead_zone: options.dead_zone.min(i16::MAX as u16) as i16,
+            config_path: options.config_path.clone(),
+            rebind: None,
+            audio,
+            muted: false,
+            rewinding: false,
+            cursor: None,
+        }
+    }
+}
+
+impl Sdl {
+    // A CHIP-8 key is down while any key or button bound to it is held
+    fn update_key(&self, chip8: &mut Chip8, k: u8) {
+        let key_held = self.held.iter().any(|s| self.scancodes.get(s) == Some(&k));
+        let button_held = self
+            .held_buttons
+            .iter()
+            .chain(&self.stick)
+            .any(|(_, b)| self.buttons.get(b) == Some(&k));
+
+        chip8.keys[k as usize] = key_held || button_held;
+    }
+
+    fn update_button(&mut self, chip8: &mut Chip8, which: u32, button: Button, down: bool) {
+        if down {
+            self.held_buttons.insert((which, button));
+        } else {
+            self.held_buttons.remove(&(which, button));
+        }
+        if let Some(&k) = self.buttons.get(&button) {
+            self.update_key(chip8, k);
+        }
+    }
+
+    // The left stick acts as the D-pad once pushed past the dead zone
+    fn update_stick(&mut self, chip8: &mut Chip8, which: u32, axis: Axis, value: i16) {
+        let (negative, positive) = match axis {
+            Axis::LeftX => (Button::DPadLeft, Button::DPadRight),
+            Axis::LeftY => (Button::DPadUp, Button::DPadDown),
+            _ => return,
+        };
+
+        for (button, pushed) in [
+            (negative, value < -self.dead_zone),
+            (positive, value > self.dead_zone),
+        ] {
+            if pushed {
+                self.stick.insert((which, button));
+            } else {
+                self.stick.remove(&(which, button));
+            }
+            if let Some(&k) = self.buttons.get(&button) {
+                self.update_key(chip8, k);
+            }
+        }
+    }
+
+    fn add_controller(&mut self, index: u32) {
+        match self.controller_subsystem.open(index) {
+            Ok(controller) => {
+                self.controllers
+                    .insert(controller.instance_id(), controller);
+            }
+            Err(err) => eprintln!("Opening game controller {}: {}", index, err),
+        }
+    }
+
+    fn remove_controller(&mut self, chip8: &mut Chip8, which: u32) {
+        self.controllers.remove(&which);
+        self.held_buttons.retain(|&(id, _)| id != which);
+        self.stick.retain(|&(id, _)| id != which);
+        for k in 0..16 {
+            self.update_key(chip8, k);
+        }
+    }
+
+    fn start_rebind(&mut self, chip8: &mut Chip8) {
+        self.rebind = Some(Rebind {
+            index: 0,
+            keymap: self.keymap.clone(),
+            pressed: false,
+            was_paused: chip8.debugger.paused,
+        });
+        chip8.debugger.pause();
+        chip8.keys = [false; 16];
+        self.held.clear();
+    }
+
+    fn rebind_key(&mut self, chip8: &mut Chip8, scancode: Scancode) {
+        let Some(rebind) = &mut self.rebind else {
+            return;
+        };
+
+        match scancode {
+            Scancode::Escape => {}
+            Scancode::Return => {
+                rebind.index += 1;
+                rebind.pressed = false;
+                if rebind.index < KeyMap::LAYOUT.len() {
+                    return;
+                }
+
+                self.keymap = rebind.keymap.clone();
+                self.scancodes = scancodes(&self.keymap);
+                if let Some(path) = &self.config_path {
+                    if let Err(err) = config::save_keys(path, &self.keymap) {
+                        eprintln!("Saving keys: {}", err);
+                    }
+                }
+            }
+            _ => {
+                let names = &mut rebind.keymap.0[KeyMap::LAYOUT[rebind.index] as usize];
+                if !rebind.pressed {
+                    names.clear();
+                    rebind.pressed = true;
+                }
+
+                let name = scancode.name().to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+                return;
+            }
+        }
+
+        // Finished or cancelled with Escape
+        if !rebind.was_paused {
+            chip8.debugger.resume();
+        }
+        self.rebind = None;
+    }
+}
+
+impl Frontend for Sdl {
+    fn poll_input(&mut self, chip8: &mut Chip8) -> bool {
+        let mut running = true;
+
+        let debugger_id = self.dcanvas.as_ref().map(|c| c.window().id());
+
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for evt in events {
             match evt {
-                Event::Quit { .. } => r = true,
+                Event::Quit { .. } => running = false,
+                Event::KeyDown {
+                    keycode: Some(key),
+                    window_id,
+                    ..
+                } if Some(window_id) == debugger_id => {
+                    let cursor = self.cursor.unwrap_or(chip8.pc);
+                    match key {
+                        Keycode::Escape => running = false,
+                        Keycode::Space if chip8.debugger.paused => chip8.debugger.resume(),
+                        Keycode::Space => chip8.debugger.pause(),
+                        Keycode::S => chip8.debugger.step(),
+                        Keycode::O => chip8.step_over(),
+                        Keycode::U => chip8.step_out(),
+                        Keycode::Up => self.cursor = Some(cursor.wrapping_sub(2)),
+                        Keycode::Down => self.cursor = Some(cursor.wrapping_add(2)),
+                        Keycode::PageUp => {
+                            self.cursor = Some(cursor.wrapping_sub(2 * MEMORY_LINES))
+                        }
+                        Keycode::PageDown => {
+                            self.cursor = Some(cursor.wrapping_add(2 * MEMORY_LINES))
+                        }
+                        Keycode::Home => self.cursor = None,
+                        Keycode::B => chip8.debugger.toggle_breakpoint(cursor),
+                        Keycode::G => chip8.debugger.run_to(cursor),
+                        Keycode::W | Keycode::R => chip8.debugger.toggle_watchpoint(Watchpoint {
+                            start: cursor,
+                            end: cursor.wrapping_add(1),
+                            read: key == Keycode::R,
+                            write: key == Keycode::W,
+                        }),
+                        Keycode::C if chip8.rom_size > 0 => {
+                            chip8.debugger.toggle_watchpoint(Watchpoint {
+                                start: ADDR_INIT,
+                                end: ADDR_INIT + (chip8.rom_size - 1) as u16,
+                                read: false,
+                                write: true,
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    ..
+                } if self.rebind.is_some() => self.rebind_key(chip8, scancode),
                 Event::KeyDown {
-                    keycode: Some(key), ..
+                    keycode: Some(key),
+                    scancode,
+                    keymod,
+                    ..
                 } => match key {
-                    Keycode::Escape => r = true,
-                    Keycode::X => chip8.keys[0] = true,
-                    Keycode::Num1 => chip8.keys[1] = true,
-                    Keycode::Num2 => chip8.keys[2] = true,
-                    Keycode::Num3 => chip8.keys[3] = true,
-                    Keycode::Q => chip8.keys[4] = true,
-                    Keycode::W => chip8.keys[5] = true,
-                    Keycode::E => chip8.keys[6] = true,
-                    Keycode::A => chip8.keys[7] = true,
-                    Keycode::S => chip8.keys[8] = true,
-                    Keycode::D => chip8.keys[9] = true,
-                    Keycode::Z => chip8.keys[0xA] = true,
-                    Keycode::C => chip8.keys[0xB] = true,
-                    Keycode::Num4 => chip8.keys[0xC] = true,
-                    Keycode::R => chip8.keys[0xD] = true,
-                    Keycode::F => chip8.keys[0xE] = true,
-                    Keycode::V => chip8.keys[0xF] = true,
-                    _ => {}
+                    Keycode::Escape => running = false,
+                    Keycode::Backspace => self.rewinding = true,
+                    Keycode::F10 => self.start_rebind(chip8),
+                    Keycode::F12 => {
+                        self.muted = !self.muted;
+                        self.stop_tone();
+                    }
+                    _ if SLOT_KEYS.contains(&key) => {
+                        let slot = SLOT_KEYS.iter().position(|&k| k == key).unwrap() + 1;
+                        let result = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            chip8.load_slot(&self.rom_path, slot)
+                        } else {
+                            chip8.save_slot(&self.rom_path, slot)
+                        };
+
+                        if let Err(err) = result {
+                            eprintln!("Slot {}: {}", slot, err);
+                        }
+                    }
+                    _ => {
+                        if let Some(&k) = scancode.and_then(|s| self.scancodes.get(&s)) {
+                            self.held.insert(scancode.unwrap());
+                            self.update_key(chip8, k);
+                        }
+                    }
                 },
                 Event::KeyUp {
-                    keycode: Some(key), ..
+                    keycode: Some(key),
+                    scancode,
+                    ..
                 } => match key {
-                    Keycode::X => chip8.keys[0] = false,
-                    Keycode::Num1 => chip8.keys[1] = false,
-                    Keycode::Num2 => chip8.keys[2] = false,
-                    Keycode::Num3 => chip8.keys[3] = false,
-                    Keycode::Q => chip8.keys[4] = false,
-                    Keycode::W => chip8.keys[5] = false,
-                    Keycode::E => chip8.keys[6] = false,
-                    Keycode::A => chip8.keys[7] = false,
-                    Keycode::S => chip8.keys[8] = false,
-                    Keycode::D => chip8.keys[9] = false,
-                    Keycode::Z => chip8.keys[0xA] = false,
-                    Keycode::C => chip8.keys[0xB] = false,
-                    Keycode::Num4 => chip8.keys[0xC] = false,
-                    Keycode::R => chip8.keys[0xD] = false,
-                    Keycode::F => chip8.keys[0xE] = false,
-                    Keycode::V => chip8.keys[0xF] = false,
-                    _ => {}
+                    Keycode::Backspace => self.rewinding = false,
+                    _ => {
+                        if let Some(&k) = scancode.and_then(|s| self.scancodes.get(&s)) {
+                            self.held.remove(&scancode.unwrap());
+                            self.update_key(chip8, k);
+                        }
+                    }
                 },
+                Event::ControllerDeviceAdded { which, .. } => self.add_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.remove_controller(chip8, which)
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    self.update_button(chip8, which, button, true)
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    self.update_button(chip8, which, button, false)
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => self.update_stick(chip8, which, axis, value),
                 _ => {}
             }
         }
 
-        for _ in 0..10 {
-            chip8.cycle();
+        running
+    }
+
+    fn present(&mut self, chip8: &Chip8) {
+        draw(chip8, &self.palette, &mut self.canvas);
+        if let Some(rebind) = &self.rebind {
+            draw_rebind(rebind, &mut self.canvas, &self.ttf_context);
+        }
+        self.canvas.present();
+        if let Some(dcanvas) = &mut self.dcanvas {
+            debug(
+                chip8,
+                self.error.as_deref(),
+                self.cursor.unwrap_or(chip8.pc),
+                dcanvas,
+                &self.ttf_context,
+            );
+        }
+
+        // Pace frames by wall clock; if the host falls behind, drop the lost time
+        // rather than running a burst of frames to catch up.
+        self.deadline += Duration::from_secs(1) / TIMER_HZ;
+        let now = Instant::now();
+        if self.deadline > now {
+            std::thread::sleep(self.deadline - now);
+        } else {
+            self.deadline = now;
         }
+    }
+
+    fn play_tone(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            audio.lock().0.playing = !self.muted;
+        }
+    }
+
+    fn stop_tone(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            audio.lock().0.playing = false;
+        }
+    }
+
+    // Errors are only shown in the debugger window; without it, quit and report them
+    fn error(&mut self, err: &Chip8Error) -> bool {
+        self.error = Some(err.to_string());
+        self.dcanvas.is_some()
+    }
 
-        draw(&chip8.display, &mut canvas);
-        debug(chip8, &mut dcanvas, &ttf_context);
+    fn clear_error(&mut self) {
+        self.error = None;
+    }
+
+    fn rewind_capacity(&self) -> usize {
+        REWIND_SECONDS * TIMER_HZ as usize
+    }
 
-        std::thread::sleep(std::time::Duration::from_millis(delay));
+    fn rewinding(&self) -> bool {
+        self.rewinding
     }
 }
 
-fn draw(display: &[bool; 64 * 32], canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+pub fn emulate(
+    title: &str,
+    rom_path: &Path,
+    chip8: &mut Chip8,
+    options: &Options,
+) -> Result<(), Chip8Error> {
+    let mut sdl = Sdl::new(title, rom_path, options);
+    frontend::run(chip8, &mut sdl)
+}
+
+fn color(palette: &Palette, px: u8) -> Color {
+    let [r, g, b] = palette.0[px as usize & 0x3];
+    Color::RGB(r, g, b)
+}
+
+fn draw(chip8: &Chip8, palette: &Palette, canvas: &mut Canvas<Window>) {
+    let (width, height) = (chip8.width(), chip8.height());
+    let scale = canvas.window().size().0 / width as u32;
+
+    canvas.set_draw_color(color(palette, 0));
     canvas.clear();
 
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for (i, &px) in display.iter().enumerate() {
-        if px {
-            let x = (i % 64) as i32;
-            let y = (i / 64) as i32;
+    for (i, &px) in chip8.display[..width * height].iter().enumerate() {
+        if px != 0 {
+            let x = (i % width) as i32;
+            let y = (i / width) as i32;
 
-            let rect = Rect::new(x * SCALE as i32, y * SCALE as i32, SCALE, SCALE);
+            canvas.set_draw_color(color(palette, px));
+            let rect = Rect::new(x * scale as i32, y * scale as i32, scale, scale);
             canvas.fill_rect(rect).unwrap();
         }
     }
-    canvas.present();
+}
+
+fn draw_rebind(
+    rebind: &Rebind,
+    canvas: &mut Canvas<Window>,
+    ttf_context: &sdl2::ttf::Sdl2TtfContext,
+) {
+    let font = ttf_context
+        .load_font("src/assets/consolas.ttf", 16)
+        .unwrap();
+    let k = KeyMap::LAYOUT[rebind.index];
+
+    canvas.set_draw_color(Color::RGB(30, 30, 30));
+    canvas.fill_rect(Rect::new(10, 10, 420, 90)).unwrap();
+
+    let lines = [
+        format!("Press keys for CHIP-8 key {:X}", k),
+        format!("Bound: {}", rebind.keymap.0[k as usize].join(", ")),
+        "Enter: next key  Esc: cancel".to_string(),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        let color = if i == 2 {
+            Color::RGB(150, 150, 150)
+        } else {
+            Color::RGB(255, 255, 255)
+        };
+        draw_text(canvas, &font, line, 20, 18 + i as i32 * 25, color);
+    }
 }
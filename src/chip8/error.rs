@@ -0,0 +1,51 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { addr: usize },
+    RomTooLarge { size: usize },
+    InvalidSaveState(&'static str),
+    SaveStateRomMismatch,
+    InvalidConfig(String),
+    InvalidTrace(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode {:04X} at {:03X}", opcode, pc)
+            }
+            Chip8Error::StackOverflow { pc } => write!(f, "Stack overflow at {:03X}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack underflow at {:03X}", pc),
+            Chip8Error::MemoryOutOfBounds { addr } => {
+                write!(f, "Memory access out of bounds: {:#X}", addr)
+            }
+            Chip8Error::RomTooLarge { size } => write!(f, "ROM too large: {} bytes", size),
+            Chip8Error::InvalidSaveState(reason) => write!(f, "Invalid save state: {}", reason),
+            Chip8Error::SaveStateRomMismatch => write!(f, "Save state belongs to a different ROM"),
+            Chip8Error::InvalidConfig(reason) => write!(f, "Invalid config: {}", reason),
+            Chip8Error::InvalidTrace(reason) => write!(f, "Invalid trace: {}", reason),
+            Chip8Error::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Chip8Error {
+    fn from(err: std::io::Error) -> Self {
+        Chip8Error::Io(err)
+    }
+}
@@ -0,0 +1,133 @@
+use super::audio::ToneGenerator;
+use super::cpu::Chip8;
+use super::error::Chip8Error;
+use super::rewind::Rewind;
+
+pub trait Frontend {
+    // Updates chip8.keys from pending input; returns false once the user asks to quit
+    fn poll_input(&mut self, chip8: &mut Chip8) -> bool;
+
+    fn present(&mut self, chip8: &Chip8);
+
+    fn play_tone(&mut self);
+
+    fn stop_tone(&mut self);
+
+    // Called when the CPU fails; return true to keep presenting the halted machine
+    fn error(&mut self, _err: &Chip8Error) -> bool {
+        false
+    }
+
+    // Called when rewinding steps back to before the error that halted the machine
+    fn clear_error(&mut self) {}
+
+    // Number of frames of history to keep for rewinding; 0 disables it
+    fn rewind_capacity(&self) -> usize {
+        0
+    }
+
+    // True while the user holds the rewind key
+    fn rewinding(&self) -> bool {
+        false
+    }
+}
+
+pub fn run<F: Frontend>(chip8: &mut Chip8, frontend: &mut F) -> Result<(), Chip8Error> {
+    let mut error = None;
+    let mut rewind = Rewind::new(frontend.rewind_capacity());
+
+    while frontend.poll_input(chip8) && !chip8.halted {
+        if frontend.rewinding() {
+            frontend.stop_tone();
+
+            // Keep the live key state so keys held while rewinding don't get stuck
+            let keys = chip8.keys;
+            if let Some(state) = rewind.pop() {
+                chip8.load_state(state)?;
+                chip8.keys = keys;
+                if error.take().is_some() {
+                    frontend.clear_error();
+                }
+            }
+        } else if error.is_none() {
+            let cycles = chip8.cycles;
+
+            match chip8.run_frame() {
+                Ok(()) if chip8.st > 0 && !chip8.debugger.paused => frontend.play_tone(),
+                Ok(()) => frontend.stop_tone(),
+                Err(err) => {
+                    frontend.stop_tone();
+                    chip8.debugger.pause();
+                    let keep_open = frontend.error(&err);
+                    error = Some(err);
+                    if !keep_open {
+                        break;
+                    }
+                }
+            }
+
+            // Don't fill the history with identical frames while paused in the debugger
+            if frontend.rewind_capacity() > 0 && chip8.cycles != cycles {
+                rewind.push(chip8.save_state());
+            }
+        }
+
+        frontend.present(chip8);
+    }
+
+    frontend.stop_tone();
+    error.map_or(Ok(()), Err)
+}
+
+// Runs a fixed number of frames without any window or input. With a tone generator,
+// each frame's audio is rendered into `samples`.
+pub struct Headless {
+    pub frames: u32,
+    pub audio: Option<ToneGenerator>,
+    pub samples: Vec<f32>,
+}
+
+impl Headless {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            audio: None,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn with_audio(frames: u32, audio: ToneGenerator) -> Self {
+        Self {
+            audio: Some(audio),
+            ..Self::new(frames)
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn poll_input(&mut self, _chip8: &mut Chip8) -> bool {
+        if self.frames == 0 {
+            return false;
+        }
+        self.frames -= 1;
+        true
+    }
+
+    fn present(&mut self, _chip8: &Chip8) {
+        if let Some(audio) = &mut self.audio {
+            audio.render_frame(&mut self.samples);
+        }
+    }
+
+    fn play_tone(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            audio.playing = true;
+        }
+    }
+
+    fn stop_tone(&mut self) {
+        if let Some(audio) = &mut self.audio {
+            audio.playing = false;
+        }
+    }
+}
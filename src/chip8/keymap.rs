@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+// Keyboard keys (by SDL scancode name) or game controller buttons (by SDL button name)
+// that press each CHIP-8 key. Scancodes name the key's position on a US layout, so the
+// default stays a 4x4 block on AZERTY or Dvorak.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap(pub [Vec<String>; 16]);
+
+impl KeyMap {
+    // The COSMAC VIP keypad, row by row; the order keys are asked for when rebinding
+    pub const LAYOUT: [u8; 16] = [
+        0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+    ];
+
+    // D-pad on 5/7/8/9 (up/left/down/right, as most games use them), A on 6, B on 4,
+    // X on 0xA, Y on 0xB and the shoulders on 1 and 2
+    pub fn gamepad() -> KeyMap {
+        let mut keymap = KeyMap(Default::default());
+        let buttons = [
+            (0x5, "dpup"),
+            (0x7, "dpleft"),
+            (0x8, "dpdown"),
+            (0x9, "dpright"),
+            (0x6, "a"),
+            (0x4, "b"),
+            (0xA, "x"),
+            (0xB, "y"),
+            (0x1, "leftshoulder"),
+            (0x2, "rightshoulder"),
+        ];
+        for (k, name) in buttons {
+            keymap.0[k].push(name.to_string());
+        }
+        keymap
+    }
+
+    // The config's `[keys]` or `[buttons]` entries replace whole CHIP-8 keys
+    pub fn with_overrides(mut self, overrides: &BTreeMap<u8, Vec<String>>) -> KeyMap {
+        for (&k, names) in overrides {
+            self.0[k as usize & 0xF] = names.clone();
+        }
+        self
+    }
+
+    // The `[keys]` table as written to the config file
+    pub fn to_table(&self) -> BTreeMap<String, Vec<String>> {
+        (0..16)
+            .map(|k| (format!("{:X}", k), self.0[k].clone()))
+            .collect()
+    }
+}
+
+// QWERTY 1234/QWER/ASDF/ZXCV
+impl Default for KeyMap {
+    fn default() -> Self {
+        let names = [
+            "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+        ];
+        KeyMap(names.map(|name| vec![name.to_string()]))
+    }
+}
@@ -1,7 +1,9 @@
-use std::fs;
+use super::error::Chip8Error;
 
+pub const RAM_SIZE: usize = 0x10000;
 pub const ADDR_INIT: u16 = 0x200;
 pub const FONTSET_ADDR_INIT: u8 = 0x50;
+pub const BIG_FONTSET_ADDR_INIT: u8 = 0xA0;
 
 pub const FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -22,15 +24,40 @@ pub const FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-pub fn load_rom(ram: &mut [u8; 4096], filename: &str) {
-    let contents = fs::read(filename).expect("Failed opening ROM file");
+pub const BIG_FONTSET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+pub fn load_rom(ram: &mut [u8; RAM_SIZE], contents: &[u8]) -> Result<(), Chip8Error> {
+    if contents.len() > ram.len() - ADDR_INIT as usize {
+        return Err(Chip8Error::RomTooLarge {
+            size: contents.len(),
+        });
+    }
+
     for (i, &byte) in contents.iter().enumerate() {
         ram[ADDR_INIT as usize + i] = byte;
     }
+    Ok(())
 }
 
-pub fn load_fontset(ram: &mut [u8; 4096], fontset: &[u8]) {
+pub fn load_fontset(ram: &mut [u8; RAM_SIZE], fontset: &[u8], addr: u8) {
     for (i, &byte) in fontset.iter().enumerate() {
-        ram[FONTSET_ADDR_INIT as usize + i] = byte;
+        ram[addr as usize + i] = byte;
     }
 }
@@ -1,5 +1,23 @@
+pub mod asm;
+pub mod audio;
+pub mod config;
 pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+#[cfg(feature = "sdl")]
 pub mod display;
+pub mod error;
+pub mod frontend;
+pub mod keymap;
 pub mod memory;
 pub mod opcodes;
+pub mod palette;
+pub mod quirks;
+pub mod rewind;
+pub mod screenshot;
+pub mod state;
+pub mod trace;
+#[cfg(feature = "tty")]
+pub mod tty;
 pub mod utils;
+pub mod wav;
@@ -1,37 +1,296 @@
-#[derive(Debug)]
-pub enum Opcode {
-    OP00E0, // CLS
-    OP00EE, // RET
-    OP1NNN, // JP addr
-    OP2NNN, // CALL addr
-    OP3XKK, // SE Vx, byte
-    OP4XKK, // SNE Vx, byte
-    OP5XY0, // SE Vx, Vy
-    OP6XKK, // LD Vx, byte
-    OP7XKK, // ADD Vx, byte
-    OP8XY0, // LD Vx, Vy
-    OP8XY1, // OR Vx, Vy
-    OP8XY2, // AND Vx, Vy
-    OP8XY3, // XOR Vx, Vy
-    OP8XY4, // ADD Vx, Vy
-    OP8XY5, // SUB Vx, Vy
-    OP8XY6, // SHR Vx {, Vy}
-    OP8XY7, // SUBN Vx, Vy
-    OP8XYE, // SHL Vx {, Vy}
-    OP9XY0, // SNE Vx, Vy
-    OPANNN, // LD I, addr
-    OPBNNN, // JP V0, addr
-    OPCXKK, // RND Vx, byte
-    OPDXYN, // DRW Vx, Vy, nibble
-    OPEX9E, // SKP Vx
-    OPEXA1, // SKNP Vx
-    OPFX07, // LD Vx, DT
-    OPFX0A, // LD Vx, K
-    OPFX15, // LD DT, Vx
-    OPFX18, // LD ST, Vx
-    OPFX1E, // ADD I, Vx
-    OPFX29, // LD F, Vx
-    OPFX33, // LD B, Vx
-    OPFX55, // LD [I], Vx
-    OPFX65, // LD Vx, [I]
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+// One of V0-VF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Reg(pub u8);
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V{:X}", self.0)
+    }
+}
+
+impl Index<Reg> for [u8; 16] {
+    type Output = u8;
+
+    fn index(&self, r: Reg) -> &u8 {
+        &self[r.0 as usize]
+    }
+}
+
+impl IndexMut<Reg> for [u8; 16] {
+    fn index_mut(&mut self, r: Reg) -> &mut u8 {
+        &mut self[r.0 as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Cls,                            // 00E0 CLS
+    Ret,                            // 00EE RET
+    ScrollDown { n: u8 },           // 00CN SCD nibble
+    ScrollUp { n: u8 },             // 00DN SCU nibble
+    ScrollRight,                    // 00FB SCR
+    ScrollLeft,                     // 00FC SCL
+    Exit,                           // 00FD EXIT
+    Low,                            // 00FE LOW
+    High,                           // 00FF HIGH
+    Jump { nnn: u16 },              // 1NNN JP addr
+    Call { nnn: u16 },              // 2NNN CALL addr
+    SkipEqImm { x: Reg, kk: u8 },   // 3XKK SE Vx, byte
+    SkipNeImm { x: Reg, kk: u8 },   // 4XKK SNE Vx, byte
+    SkipEq { x: Reg, y: Reg },      // 5XY0 SE Vx, Vy
+    SaveRange { x: Reg, y: Reg },   // 5XY2 SAVE Vx - Vy
+    LoadRange { x: Reg, y: Reg },   // 5XY3 LOAD Vx - Vy
+    LoadImm { x: Reg, kk: u8 },     // 6XKK LD Vx, byte
+    AddImm { x: Reg, kk: u8 },      // 7XKK ADD Vx, byte
+    Load { x: Reg, y: Reg },        // 8XY0 LD Vx, Vy
+    Or { x: Reg, y: Reg },          // 8XY1 OR Vx, Vy
+    And { x: Reg, y: Reg },         // 8XY2 AND Vx, Vy
+    Xor { x: Reg, y: Reg },         // 8XY3 XOR Vx, Vy
+    Add { x: Reg, y: Reg },         // 8XY4 ADD Vx, Vy
+    Sub { x: Reg, y: Reg },         // 8XY5 SUB Vx, Vy
+    ShiftRight { x: Reg, y: Reg },  // 8XY6 SHR Vx {, Vy}
+    SubN { x: Reg, y: Reg },        // 8XY7 SUBN Vx, Vy
+    ShiftLeft { x: Reg, y: Reg },   // 8XYE SHL Vx {, Vy}
+    SkipNe { x: Reg, y: Reg },      // 9XY0 SNE Vx, Vy
+    LoadI { nnn: u16 },             // ANNN LD I, addr
+    JumpV0 { nnn: u16 },            // BNNN JP V0, addr
+    Random { x: Reg, kk: u8 },      // CXKK RND Vx, byte
+    Draw { x: Reg, y: Reg, n: u8 }, // DXYN DRW Vx, Vy, nibble (DXY0: 16x16 sprite)
+    SkipKey { x: Reg },             // EX9E SKP Vx
+    SkipNotKey { x: Reg },          // EXA1 SKNP Vx
+    LoadILong { nnnn: u16 },        // F000 NNNN LD I, long addr
+    Plane { n: u8 },                // FN01 PLANE n
+    Audio,                          // F002 AUDIO
+    LoadDelay { x: Reg },           // FX07 LD Vx, DT
+    WaitKey { x: Reg },             // FX0A LD Vx, K
+    SetDelay { x: Reg },            // FX15 LD DT, Vx
+    SetSound { x: Reg },            // FX18 LD ST, Vx
+    AddI { x: Reg },                // FX1E ADD I, Vx
+    LoadFont { x: Reg },            // FX29 LD F, Vx
+    LoadBigFont { x: Reg },         // FX30 LD HF, Vx
+    Bcd { x: Reg },                 // FX33 LD B, Vx
+    Pitch { x: Reg },               // FX3A PITCH Vx
+    Store { x: Reg },               // FX55 LD [I], Vx
+    Restore { x: Reg },             // FX65 LD Vx, [I]
+    SaveFlags { x: Reg },           // FX75 LD R, Vx
+    LoadFlags { x: Reg },           // FX85 LD Vx, R
+}
+
+// `next` is the word following the opcode, only used by F000 NNNN
+pub fn decode(opcode: u16, next: u16) -> Option<Instruction> {
+    use Instruction::*;
+
+    let x = Reg(((opcode & 0xF00) >> 8) as u8);
+    let y = Reg(((opcode & 0xF0) >> 4) as u8);
+    let n = (opcode & 0xF) as u8;
+    let kk = (opcode & 0xFF) as u8;
+    let nnn = opcode & 0xFFF;
+
+    let instruction = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Cls,
+            0x00EE => Ret,
+            0x00FB => ScrollRight,
+            0x00FC => ScrollLeft,
+            0x00FD => Exit,
+            0x00FE => Low,
+            0x00FF => High,
+            _ if opcode & 0xFFF0 == 0x00C0 => ScrollDown { n },
+            _ if opcode & 0xFFF0 == 0x00D0 => ScrollUp { n },
+            _ => return None,
+        },
+        0x1000 => Jump { nnn },
+        0x2000 => Call { nnn },
+        0x3000 => SkipEqImm { x, kk },
+        0x4000 => SkipNeImm { x, kk },
+        0x5000 => match n {
+            0x0 => SkipEq { x, y },
+            0x2 => SaveRange { x, y },
+            0x3 => LoadRange { x, y },
+            _ => return None,
+        },
+        0x6000 => LoadImm { x, kk },
+        0x7000 => AddImm { x, kk },
+        0x8000 => match n {
+            0x0 => Load { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => Add { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubN { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => return None,
+        },
+        0x9000 if n == 0 => SkipNe { x, y },
+        0xA000 => LoadI { nnn },
+        0xB000 => JumpV0 { nnn },
+        0xC000 => Random { x, kk },
+        0xD000 => Draw { x, y, n },
+        0xE000 => match kk {
+            0x9E => SkipKey { x },
+            0xA1 => SkipNotKey { x },
+            _ => return None,
+        },
+        0xF000 => match kk {
+            0x00 if opcode == 0xF000 => LoadILong { nnnn: next },
+            0x01 => Plane { n: x.0 },
+            0x02 if opcode == 0xF002 => Audio,
+            0x07 => LoadDelay { x },
+            0x0A => WaitKey { x },
+            0x15 => SetDelay { x },
+            0x18 => SetSound { x },
+            0x1E => AddI { x },
+            0x29 => LoadFont { x },
+            0x30 => LoadBigFont { x },
+            0x33 => Bcd { x },
+            0x3A => Pitch { x },
+            0x55 => Store { x },
+            0x65 => Restore { x },
+            0x75 => SaveFlags { x },
+            0x85 => LoadFlags { x },
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+impl Instruction {
+    // Length in bytes; F000 NNNN is the only 4-byte instruction
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::LoadILong { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    // Big-endian bytes as they appear in a ROM; decode(encode()) gives back self
+    pub fn encode(self) -> Vec<u8> {
+        use Instruction::*;
+
+        let xy = |x: Reg, y: Reg| (x.0 as u16) << 8 | (y.0 as u16) << 4;
+        let xkk = |x: Reg, kk: u8| (x.0 as u16) << 8 | kk as u16;
+        let x = |x: Reg| (x.0 as u16) << 8;
+
+        let word = match self {
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollDown { n } => 0x00C0 | (n & 0xF) as u16,
+            ScrollUp { n } => 0x00D0 | (n & 0xF) as u16,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Low => 0x00FE,
+            High => 0x00FF,
+            Jump { nnn } => 0x1000 | nnn & 0xFFF,
+            Call { nnn } => 0x2000 | nnn & 0xFFF,
+            SkipEqImm { x, kk } => 0x3000 | xkk(x, kk),
+            SkipNeImm { x, kk } => 0x4000 | xkk(x, kk),
+            SkipEq { x, y } => 0x5000 | xy(x, y),
+            SaveRange { x, y } => 0x5002 | xy(x, y),
+            LoadRange { x, y } => 0x5003 | xy(x, y),
+            LoadImm { x, kk } => 0x6000 | xkk(x, kk),
+            AddImm { x, kk } => 0x7000 | xkk(x, kk),
+            Load { x, y } => 0x8000 | xy(x, y),
+            Or { x, y } => 0x8001 | xy(x, y),
+            And { x, y } => 0x8002 | xy(x, y),
+            Xor { x, y } => 0x8003 | xy(x, y),
+            Add { x, y } => 0x8004 | xy(x, y),
+            Sub { x, y } => 0x8005 | xy(x, y),
+            ShiftRight { x, y } => 0x8006 | xy(x, y),
+            SubN { x, y } => 0x8007 | xy(x, y),
+            ShiftLeft { x, y } => 0x800E | xy(x, y),
+            SkipNe { x, y } => 0x9000 | xy(x, y),
+            LoadI { nnn } => 0xA000 | nnn & 0xFFF,
+            JumpV0 { nnn } => 0xB000 | nnn & 0xFFF,
+            Random { x, kk } => 0xC000 | xkk(x, kk),
+            Draw { x, y, n } => 0xD000 | xy(x, y) | (n & 0xF) as u16,
+            SkipKey { x: r } => 0xE09E | x(r),
+            SkipNotKey { x: r } => 0xE0A1 | x(r),
+            LoadILong { nnnn } => return vec![0xF0, 0x00, (nnnn >> 8) as u8, nnnn as u8],
+            Plane { n } => 0xF001 | ((n & 0xF) as u16) << 8,
+            Audio => 0xF002,
+            LoadDelay { x: r } => 0xF007 | x(r),
+            WaitKey { x: r } => 0xF00A | x(r),
+            SetDelay { x: r } => 0xF015 | x(r),
+            SetSound { x: r } => 0xF018 | x(r),
+            AddI { x: r } => 0xF01E | x(r),
+            LoadFont { x: r } => 0xF029 | x(r),
+            LoadBigFont { x: r } => 0xF030 | x(r),
+            Bcd { x: r } => 0xF033 | x(r),
+            Pitch { x: r } => 0xF03A | x(r),
+            Store { x: r } => 0xF055 | x(r),
+            Restore { x: r } => 0xF065 | x(r),
+            SaveFlags { x: r } => 0xF075 | x(r),
+            LoadFlags { x: r } => 0xF085 | x(r),
+        };
+
+        vec![(word >> 8) as u8, word as u8]
+    }
+}
+
+// Cowgod-style mnemonics, e.g. "DRW V1, V2, 5"
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollDown { n } => write!(f, "SCD {}", n),
+            ScrollUp { n } => write!(f, "SCU {}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            Jump { nnn } => write!(f, "JP 0x{:03X}", nnn),
+            Call { nnn } => write!(f, "CALL 0x{:03X}", nnn),
+            SkipEqImm { x, kk } => write!(f, "SE {}, 0x{:02X}", x, kk),
+            SkipNeImm { x, kk } => write!(f, "SNE {}, 0x{:02X}", x, kk),
+            SkipEq { x, y } => write!(f, "SE {}, {}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE {} - {}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD {} - {}", x, y),
+            LoadImm { x, kk } => write!(f, "LD {}, 0x{:02X}", x, kk),
+            AddImm { x, kk } => write!(f, "ADD {}, 0x{:02X}", x, kk),
+            Load { x, y } => write!(f, "LD {}, {}", x, y),
+            Or { x, y } => write!(f, "OR {}, {}", x, y),
+            And { x, y } => write!(f, "AND {}, {}", x, y),
+            Xor { x, y } => write!(f, "XOR {}, {}", x, y),
+            Add { x, y } => write!(f, "ADD {}, {}", x, y),
+            Sub { x, y } => write!(f, "SUB {}, {}", x, y),
+            ShiftRight { x, y } => write!(f, "SHR {}, {}", x, y),
+            SubN { x, y } => write!(f, "SUBN {}, {}", x, y),
+            ShiftLeft { x, y } => write!(f, "SHL {}, {}", x, y),
+            SkipNe { x, y } => write!(f, "SNE {}, {}", x, y),
+            LoadI { nnn } => write!(f, "LD I, 0x{:03X}", nnn),
+            JumpV0 { nnn } => write!(f, "JP V0, 0x{:03X}", nnn),
+            Random { x, kk } => write!(f, "RND {}, 0x{:02X}", x, kk),
+            Draw { x, y, n } => write!(f, "DRW {}, {}, {}", x, y, n),
+            SkipKey { x } => write!(f, "SKP {}", x),
+            SkipNotKey { x } => write!(f, "SKNP {}", x),
+            LoadILong { nnnn } => write!(f, "LD I, long 0x{:04X}", nnnn),
+            Plane { n } => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LoadDelay { x } => write!(f, "LD {}, DT", x),
+            WaitKey { x } => write!(f, "LD {}, K", x),
+            SetDelay { x } => write!(f, "LD DT, {}", x),
+            SetSound { x } => write!(f, "LD ST, {}", x),
+            AddI { x } => write!(f, "ADD I, {}", x),
+            LoadFont { x } => write!(f, "LD F, {}", x),
+            LoadBigFont { x } => write!(f, "LD HF, {}", x),
+            Bcd { x } => write!(f, "LD B, {}", x),
+            Pitch { x } => write!(f, "PITCH {}", x),
+            Store { x } => write!(f, "LD [I], {}", x),
+            Restore { x } => write!(f, "LD {}, [I]", x),
+            SaveFlags { x } => write!(f, "LD R, {}", x),
+            LoadFlags { x } => write!(f, "LD {}, R", x),
+        }
+    }
 }
@@ -0,0 +1,33 @@
+// RGB colours indexed by a pixel's XO-CHIP plane bits: none, plane 1, plane 2, both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [[u8; 3]; 4]);
+
+impl Palette {
+    pub const CLASSIC: Palette =
+        Palette([[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]]);
+
+    pub const AMBER: Palette = Palette([[20, 12, 0], [255, 176, 0], [180, 110, 0], [100, 60, 0]]);
+
+    pub const GREEN: Palette = Palette([[0, 20, 0], [51, 255, 51], [30, 170, 30], [15, 90, 15]]);
+
+    pub const GAMEBOY: Palette =
+        Palette([[15, 56, 15], [155, 188, 15], [139, 172, 15], [48, 98, 48]]);
+
+    pub const NAMES: [&'static str; 4] = ["classic", "amber", "green", "gameboy"];
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        match name {
+            "classic" => Some(Palette::CLASSIC),
+            "amber" => Some(Palette::AMBER),
+            "green" => Some(Palette::GREEN),
+            "gameboy" => Some(Palette::GAMEBOY),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC
+    }
+}
@@ -0,0 +1,73 @@
+// https://github.com/Timendus/chip8-test-suite#quirks-test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    // 8XY6/8XYE: shift Vy into Vx instead of shifting Vx in place
+    pub shift_vy: bool,
+    // FX55/FX65: leave I pointing past the last register
+    pub load_store_inc: bool,
+    // FX55/FX65: leave I pointing at the last register, as CHIP-48 does; ignored with
+    // load_store_inc
+    pub load_store_inc_x: bool,
+    // BNNN: behave as BXNN and jump to XNN + Vx instead of NNN + V0
+    pub jump_vx: bool,
+    // 8XY1/8XY2/8XY3: reset VF to 0
+    pub vf_reset: bool,
+    // DXYN: clip sprites at the screen edges instead of wrapping
+    pub clip_sprites: bool,
+    // DXYN: draw at most one sprite per frame (wait for vblank)
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub const VIP: Quirks = Quirks {
+        shift_vy: true,
+        load_store_inc: true,
+        load_store_inc_x: false,
+        jump_vx: false,
+        vf_reset: true,
+        clip_sprites: true,
+        display_wait: true,
+    };
+
+    pub const CHIP48: Quirks = Quirks {
+        shift_vy: false,
+        load_store_inc: false,
+        load_store_inc_x: true,
+        jump_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    pub const SCHIP: Quirks = Quirks {
+        shift_vy: false,
+        load_store_inc: false,
+        load_store_inc_x: false,
+        jump_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    pub const XOCHIP: Quirks = Quirks {
+        shift_vy: true,
+        load_store_inc: true,
+        load_store_inc_x: false,
+        jump_vx: false,
+        vf_reset: false,
+        clip_sprites: false,
+        display_wait: false,
+    };
+
+    pub const NAMES: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" => Some(Quirks::VIP),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip" => Some(Quirks::SCHIP),
+            "xochip" => Some(Quirks::XOCHIP),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+// Ring buffer of per-frame save states. Only the newest state is kept in full; every
+// older frame is stored as a delta that turns its successor back into it, so a frame
+// that only touched a few registers and display bytes costs a few dozen bytes.
+#[derive(Debug)]
+pub struct Rewind {
+    capacity: usize,
+    current: Vec<u8>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+// Delta format: a sequence of runs, each `offset u32 | len u16 | bytes`, little-endian
+fn diff(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut i = 0;
+
+    while i < from.len() {
+        if from[i] == to[i] {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < from.len() && from[i] != to[i] && i - start < u16::MAX as usize {
+            i += 1;
+        }
+        delta.extend_from_slice(&(start as u32).to_le_bytes());
+        delta.extend_from_slice(&((i - start) as u16).to_le_bytes());
+        delta.extend_from_slice(&to[start..i]);
+    }
+
+    delta
+}
+
+fn apply(state: &mut [u8], mut delta: &[u8]) {
+    while !delta.is_empty() {
+        let start = u32::from_le_bytes(delta[0..4].try_into().unwrap()) as usize;
+        let len = u16::from_le_bytes(delta[4..6].try_into().unwrap()) as usize;
+        state[start..start + len].copy_from_slice(&delta[6..6 + len]);
+        delta = &delta[6 + len..];
+    }
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            current: Vec::new(),
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.deltas.clear();
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.current.len() == state.len() {
+            self.deltas.push_back(diff(&state, &self.current));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        } else {
+            // First frame, or a state of a different format: nothing to diff against
+            self.deltas.clear();
+        }
+
+        self.current = state;
+    }
+
+    // Steps one frame back and returns that frame's state, or None once the
+    // history is exhausted
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        apply(&mut self.current, &delta);
+        Some(&self.current)
+    }
+}
@@ -0,0 +1,36 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use super::cpu::Chip8;
+use super::error::Chip8Error;
+use super::palette::Palette;
+
+// Writes the current frame as an RGB PNG, each CHIP-8 pixel drawn as a scale x scale block
+pub fn write_png(
+    chip8: &Chip8,
+    palette: &Palette,
+    scale: u32,
+    path: &Path,
+) -> Result<(), Chip8Error> {
+    let (width, height) = (chip8.width(), chip8.height());
+    let scale = scale.max(1) as usize;
+
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for y in 0..height * scale {
+        for x in 0..width * scale {
+            let px = chip8.display[(y / scale) * width + x / scale];
+            data.extend_from_slice(&palette.0[px as usize & 0x3]);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| Chip8Error::Io(io::Error::other(err)))
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::cpu::Chip8;
+use super::error::Chip8Error;
+
+// Save state layout (all integers little-endian):
+//
+//   magic "C8ST" | version u16 | ROM SHA-1 [u8; 20] | machine state
+//
+// The machine state is written field by field in the order of save_state below.
+// Bump STATE_VERSION whenever that order or any field's size changes.
+const STATE_MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u16 = 3;
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.data.len() < n {
+            return Err(Chip8Error::InvalidSaveState("truncated"));
+        }
+
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn bytes(&mut self, dst: &mut [u8]) -> Result<(), Chip8Error> {
+        dst.copy_from_slice(self.take(dst.len())?);
+        Ok(())
+    }
+}
+
+// Slots are stored next to the ROM, e.g. tetris.ch8 -> tetris.ch8.slot1
+fn slot_path(rom_path: &Path, slot: usize) -> PathBuf {
+    let mut path = rom_path.as_os_str().to_owned();
+    path.push(format!(".slot{}", slot));
+    PathBuf::from(path)
+}
+
+impl Chip8 {
+    pub fn save_slot(&self, rom_path: &Path, slot: usize) -> Result<(), Chip8Error> {
+        fs::write(slot_path(rom_path, slot), self.save_state())?;
+        Ok(())
+    }
+
+    pub fn load_slot(&mut self, rom_path: &Path, slot: usize) -> Result<(), Chip8Error> {
+        let data = fs::read(slot_path(rom_path, slot))?;
+        self.load_state(&data)
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.ram.len() + self.display.len() + 256);
+
+        out.extend_from_slice(STATE_MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash);
+
+        out.extend_from_slice(&self.reg);
+        out.extend_from_slice(&self.ireg.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.push(self.sp);
+        out.push(self.dt);
+        out.push(self.st);
+        out.extend(self.keys.iter().map(|&k| k as u8));
+        out.push(self.hires as u8);
+        out.push(self.plane);
+        out.extend_from_slice(&self.pattern);
+        out.push(self.pitch);
+        out.extend_from_slice(&self.rpl);
+        out.push(self.halted as u8);
+        out.extend_from_slice(&self.opcode.to_le_bytes());
+        out.push(self.vblank as u8);
+        out.extend_from_slice(&self.ips_remainder.to_le_bytes());
+        out.extend_from_slice(&self.frame_budget.to_le_bytes());
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+        out.extend_from_slice(&self.display);
+        out.extend_from_slice(&self.ram);
+
+        out
+    }
+
+    // Restores a state produced by save_state. The machine is only modified if the
+    // whole state is valid and was saved while running the currently loaded ROM.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut r = Reader { data };
+
+        if r.take(4)? != STATE_MAGIC {
+            return Err(Chip8Error::InvalidSaveState("bad magic"));
+        }
+        if r.u16()? != STATE_VERSION {
+            return Err(Chip8Error::InvalidSaveState("unsupported version"));
+        }
+        if r.take(20)? != self.rom_hash {
+            return Err(Chip8Error::SaveStateRomMismatch);
+        }
+
+        let mut next = Chip8::new(self.quirks);
+        next.ips = self.ips;
+        next.rom_hash = self.rom_hash;
+        next.rom_size = self.rom_size;
+
+        r.bytes(&mut next.reg)?;
+        next.ireg = r.u16()?;
+        next.pc = r.u16()?;
+        for addr in next.stack.iter_mut() {
+            *addr = r.u16()?;
+        }
+        next.sp = r.u8()?;
+        next.dt = r.u8()?;
+        next.st = r.u8()?;
+        for key in next.keys.iter_mut() {
+            *key = r.bool()?;
+        }
+        next.hires = r.bool()?;
+        next.plane = r.u8()?;
+        r.bytes(&mut next.pattern)?;
+        next.pitch = r.u8()?;
+        r.bytes(&mut next.rpl)?;
+        next.halted = r.bool()?;
+        next.opcode = r.u16()?;
+        next.vblank = r.bool()?;
+        next.ips_remainder = r.u32()?;
+        next.frame_budget = r.u32()?;
+        next.cycles = r.u64()?;
+        let rng_state = r.u64()?;
+        r.bytes(&mut next.display)?;
+        r.bytes(&mut next.ram)?;
+
+        if !r.data.is_empty() {
+            return Err(Chip8Error::InvalidSaveState("trailing data"));
+        }
+        if next.sp as usize > next.stack.len() {
+            return Err(Chip8Error::InvalidSaveState("stack pointer out of range"));
+        }
+
+        next.debugger = std::mem::take(&mut self.debugger);
+        next.recorder = self.recorder.take();
+        next.tracer = self.tracer.take();
+        // Keep the same kind of generator, rewound to where it was when saved
+        std::mem::swap(&mut next.rng, &mut self.rng);
+        next.rng.set_state(rng_state);
+        *self = next;
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use super::cpu::Chip8;
+use super::disasm;
+use super::error::Chip8Error;
+
+// One line per executed instruction, logged after fetch and before execute, so the
+// registers are the state the instruction starts from:
+//
+//   <cycle> <pc> <opcode> V=<V0..VF> I=<i> SP=<sp> DT=<dt> ST=<st> ; <disassembly>
+//
+//   1 0200 00E0 V=00000000000000000000000000000000 I=0000 SP=00 DT=00 ST=00 ; CLS
+//
+// cycle is decimal and counts from 1 (Chip8::cycles after the fetch); everything else
+// is upper-case hex: pc and I are 4 digits, the opcode is the first word only (F000
+// NNNN shows as F000), V0-VF are 2 digits each with no separator. Lines starting with
+// '#' are comments, e.g. the reason a ring buffer was dumped.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    // Only instructions at these addresses are logged; empty logs everything
+    pub ranges: Vec<RangeInclusive<u16>>,
+    // In ring mode the last `capacity` lines are kept and only written by dump()
+    ring: Option<(VecDeque<String>, usize)>,
+    // Buffered tracers keep every line for take_lines(), so dump() leaves them in place
+    keep: bool,
+    // The first write error, reported by finish()
+    error: Option<io::Error>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("ranges", &self.ranges)
+            .field("ring", &self.ring)
+            .field("keep", &self.keep)
+            .finish_non_exhaustive()
+    }
+}
+
+pub fn format_line(chip8: &Chip8, pc: u16) -> String {
+    let mut line = format!("{} {:04X} {:04X} V=", chip8.cycles, pc, chip8.opcode);
+    for v in chip8.reg {
+        write!(line, "{:02X}", v).unwrap();
+    }
+    write!(
+        line,
+        " I={:04X} SP={:02X} DT={:02X} ST={:02X} ; {}",
+        chip8.ireg,
+        chip8.sp,
+        chip8.dt,
+        chip8.st,
+        disasm::disassemble_at(&chip8.ram, pc)
+    )
+    .unwrap();
+    line
+}
+
+impl Tracer {
+    // Keeps every line in memory for take_lines(), e.g. to diff a live run
+    pub fn buffered() -> Self {
+        Self {
+            keep: true,
+            ..Self::new(Box::new(io::sink())).with_ring(usize::MAX)
+        }
+    }
+
+    pub fn take_lines(&mut self) -> Vec<String> {
+        match &mut self.ring {
+            Some((lines, _)) => std::mem::take(lines).into(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer,
+            ranges: Vec::new(),
+            ring: None,
+            keep: false,
+            error: None,
+        }
+    }
+
+    pub fn create(path: &Path) -> Result<Self, Chip8Error> {
+        Ok(Self::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+
+    // Keeps only the last `capacity` instructions, written out when dump() is called
+    pub fn with_ring(mut self, capacity: usize) -> Self {
+        self.ring = Some((VecDeque::new(), capacity));
+        self
+    }
+
+    pub fn traces(&self, pc: u16) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&pc))
+    }
+
+    pub fn log(&mut self, line: String) {
+        match &mut self.ring {
+            Some((lines, capacity)) => {
+                if lines.len() == *capacity {
+                    lines.pop_front();
+                }
+                if *capacity > 0 {
+                    lines.push_back(line);
+                }
+            }
+            None => self.write_line(&line),
+        }
+    }
+
+    // Writes out the ring buffer after a comment saying why; does nothing outside ring mode
+    // or when buffered
+    pub fn dump(&mut self, reason: impl fmt::Display) {
+        let Some((lines, _)) = &mut self.ring else {
+            return;
+        };
+        if lines.is_empty() || self.keep {
+            return;
+        }
+
+        let lines = std::mem::take(lines);
+        self.write_line(&format!("# {}", reason));
+        for line in &lines {
+            self.write_line(line);
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.writer, "{}", line) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), Chip8Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// A parsed trace line. Everything but the cycle count is compared by trace-diff, as
+// other emulators may count cycles differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceLine {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    pub text: String,
+}
+
+impl TraceLine {
+    pub fn parse(line: &str) -> Option<TraceLine> {
+        let (state, _) = line.split_once(" ; ").unwrap_or((line, ""));
+        let fields: Vec<&str> = state.split_whitespace().collect();
+        let [cycle, pc, opcode, v, i, sp, dt, st] = fields[..] else {
+            return None;
+        };
+
+        let hex = |field: &str, name: &str| u16::from_str_radix(field.strip_prefix(name)?, 16).ok();
+        let v = v
+            .strip_prefix("V=")
+            .filter(|v| v.len() == 32 && v.is_ascii())?;
+        let mut regs = [0; 16];
+        for (k, reg) in regs.iter_mut().enumerate() {
+            *reg = u8::from_str_radix(&v[k * 2..k * 2 + 2], 16).ok()?;
+        }
+
+        Some(TraceLine {
+            cycle: cycle.parse().ok()?,
+            pc: hex(pc, "")?,
+            opcode: hex(opcode, "")?,
+            v: regs,
+            i: hex(i, "I=")?,
+            sp: u8::try_from(hex(sp, "SP=")?).ok()?,
+            dt: u8::try_from(hex(dt, "DT=")?).ok()?,
+            st: u8::try_from(hex(st, "ST=")?).ok()?,
+            text: line.to_string(),
+        })
+    }
+
+    // The compared fields as (name, value) pairs
+    fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = vec![
+            ("PC".to_string(), format!("{:04X}", self.pc)),
+            ("opcode".to_string(), format!("{:04X}", self.opcode)),
+        ];
+        fields.extend((0..16).map(|k| (format!("V{:X}", k), format!("{:02X}", self.v[k]))));
+        fields.extend([
+            ("I".to_string(), format!("{:04X}", self.i)),
+            ("SP".to_string(), format!("{:02X}", self.sp)),
+            ("DT".to_string(), format!("{:02X}", self.dt)),
+            ("ST".to_string(), format!("{:02X}", self.st)),
+        ]);
+        fields
+    }
+
+    // The fields that differ, e.g. "V0: 03 != 01"
+    pub fn differences(&self, other: &TraceLine) -> Vec<String> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((name, a), (_, b))| format!("{}: {} != {}", name, a, b))
+            .collect()
+    }
+}
+
+// Parses a whole trace, skipping blank lines and '#' comments
+pub fn parse_trace(text: &str) -> Result<Vec<TraceLine>, Chip8Error> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| {
+            TraceLine::parse(line)
+                .ok_or_else(|| Chip8Error::InvalidTrace(format!("line {}: `{}`", n + 1, line)))
+        })
+        .collect()
+}
+
+pub fn read_trace(path: &Path) -> Result<Vec<TraceLine>, Chip8Error> {
+    parse_trace(&fs::read_to_string(path)?).map_err(|err| match err {
+        Chip8Error::InvalidTrace(msg) => {
+            Chip8Error::InvalidTrace(format!("{}: {}", path.display(), msg))
+        }
+        err => err,
+    })
+}
+
+// Walks both traces in lockstep and describes the first instruction where they differ,
+// preceded by up to `context` matching lines. None if the traces are the same.
+pub fn diff(expected: &[TraceLine], actual: &[TraceLine], context: usize) -> Option<String> {
+    let n = (0..expected.len().max(actual.len())).find(|&n| {
+        match (expected.get(n), actual.get(n)) {
+            (Some(a), Some(b)) => !a.differences(b).is_empty(),
+            _ => true,
+        }
+    })?;
+
+    let mut out = format!("first difference at instruction {}:\n", n + 1);
+    for line in &expected[n.saturating_sub(context)..n] {
+        writeln!(out, "  {}", line.text).unwrap();
+    }
+
+    for (sign, trace) in [('-', expected), ('+', actual)] {
+        let text = trace.get(n).map_or("(end of trace)", |line| &line.text);
+        writeln!(out, "{} {}", sign, text).unwrap();
+    }
+
+    if let (Some(a), Some(b)) = (expected.get(n), actual.get(n)) {
+        for difference in a.differences(b) {
+            writeln!(out, "{}", difference).unwrap();
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,384 @@
+extern crate crossterm;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::cpu::{Chip8, TIMER_HZ};
+use super::disasm;
+use super::error::Chip8Error;
+use super::frontend::{self, Frontend};
+use super::keymap::KeyMap;
+use super::palette::Palette;
+
+pub struct Options {
+    pub palette: Palette,
+    pub keys: KeyMap,
+    // Show the registers and step controls under the game
+    pub debugger: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            keys: KeyMap::default(),
+            debugger: true,
+        }
+    }
+}
+
+// Terminals send characters rather than key positions, so the SDL scancode names in the
+// key map are matched by the character they produce on a US layout
+fn key_code(name: &str) -> Option<KeyCode> {
+    let name = name.strip_prefix("Keypad ").unwrap_or(name);
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => return Some(KeyCode::Char(c.to_ascii_lowercase())),
+        (None, _) => return None,
+        _ => {}
+    }
+
+    match name.to_ascii_lowercase().as_str() {
+        "space" => Some(KeyCode::Char(' ')),
+        "return" | "enter" => Some(KeyCode::Enter),
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        _ => None,
+    }
+}
+
+fn key_codes(keymap: &KeyMap) -> HashMap<KeyCode, u8> {
+    let mut codes = HashMap::new();
+
+    for (k, names) in keymap.0.iter().enumerate() {
+        for name in names {
+            match key_code(name) {
+                Some(code) => {
+                    codes.insert(code, k as u8);
+                }
+                None => eprintln!("Unknown key name `{}` for CHIP-8 key {:X}", name, k),
+            }
+        }
+    }
+
+    codes
+}
+
+// Without key release events a press holds the key for this many frames, renewed by
+// the terminal's key repeat
+const HOLD_FRAMES: u64 = 10;
+
+const REWIND_SECONDS: usize = 10;
+
+pub struct Tty {
+    out: io::Stdout,
+    rom_path: PathBuf,
+    palette: Palette,
+    codes: HashMap<KeyCode, u8>,
+    debugger: bool,
+    // Whether the terminal reports key releases (the kitty keyboard protocol)
+    releases: bool,
+    // Keys held down, mapped to the frame they're released at (u64::MAX until a release
+    // event arrives)
+    held: HashMap<KeyCode, u64>,
+    frame: u64,
+    // The last screen written, so unchanged frames aren't redrawn
+    screen: String,
+    width: usize,
+    error: Option<String>,
+    // Shown under the status lines, e.g. a slot that failed to load
+    message: Option<String>,
+    // The first terminal error; stops the emulator
+    io_error: Option<io::Error>,
+    deadline: Instant,
+    beeping: bool,
+    muted: bool,
+}
+
+impl Tty {
+    pub fn new(rom_path: &Path, options: &Options) -> io::Result<Self> {
+        let codes = key_codes(&options.keys);
+
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Self {
+            out,
+            rom_path: rom_path.to_path_buf(),
+            palette: options.palette,
+            codes,
+            debugger: options.debugger,
+            releases,
+            held: HashMap::new(),
+            frame: 0,
+            screen: String::new(),
+            width: 0,
+            error: None,
+            message: None,
+            io_error: None,
+            deadline: Instant::now(),
+            beeping: false,
+            muted: false,
+        })
+    }
+
+    fn key(&mut self, chip8: &mut Chip8, key: KeyEvent) -> bool {
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+
+        match key.kind {
+            KeyEventKind::Release => {
+                self.held.remove(&code);
+                return true;
+            }
+            // Keys reported as held stay down until released; hotkeys don't repeat
+            KeyEventKind::Repeat => return true,
+            KeyEventKind::Press => {}
+        }
+
+        match code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Tab if self.debugger && chip8.debugger.paused => chip8.debugger.resume(),
+            KeyCode::Tab if self.debugger => chip8.debugger.pause(),
+            KeyCode::Char('s') if chip8.debugger.paused => chip8.debugger.step(),
+            KeyCode::Char('o') if chip8.debugger.paused => chip8.step_over(),
+            KeyCode::Char('u') if chip8.debugger.paused => chip8.step_out(),
+            KeyCode::F(12) => {
+                self.muted = !self.muted;
+                self.stop_tone();
+            }
+            // F1-F9 save to the numbered slot, Shift+F1-F9 load from it
+            KeyCode::F(n @ 1..=9) => {
+                let slot = n as usize;
+                self.message = None;
+                let result = if key.modifiers.contains(KeyModifiers::SHIFT) {
+                    chip8.load_slot(&self.rom_path, slot)
+                } else {
+                    chip8.save_slot(&self.rom_path, slot)
+                };
+
+                if let Err(err) = result {
+                    self.message = Some(format!("Slot {}: {}", slot, err));
+                }
+            }
+            _ => {
+                let until = if self.releases {
+                    u64::MAX
+                } else {
+                    self.frame + HOLD_FRAMES
+                };
+                self.held.insert(code, until);
+            }
+        }
+        true
+    }
+
+    fn draw(&self, chip8: &Chip8) -> String {
+        let (width, height) = (chip8.width(), chip8.height());
+        let mut screen = String::new();
+        let mut colors = None;
+
+        // Each character cell is two pixels: the upper half block in the top pixel's
+        // colour over the bottom pixel's
+        for y in (0..height).step_by(2) {
+            for x in 0..width {
+                let top = color(&self.palette, chip8.display[y * width + x]);
+                let bottom = color(&self.palette, chip8.display[(y + 1) * width + x]);
+                if colors != Some((top, bottom)) {
+                    screen += &format!("{}{}", SetForegroundColor(top), SetBackgroundColor(bottom));
+                    colors = Some((top, bottom));
+                }
+                screen.push('▀');
+            }
+            screen += &format!("{}\r\n", ResetColor);
+            colors = None;
+        }
+
+        if self.debugger {
+            let status = match (&self.error, chip8.debugger.paused) {
+                (Some(err), _) => err.as_str(),
+                (None, true) => "Paused",
+                (None, false) => "Running",
+            };
+            let regs = |range: std::ops::Range<usize>| {
+                range
+                    .map(|i| format!("V{:X}:{:02X}", i, chip8.reg[i]))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let lines = [
+                format!(
+                    "{:03X}: {}  [{}]",
+                    chip8.pc,
+                    disasm::disassemble_at(&chip8.ram, chip8.pc),
+                    status
+                ),
+                regs(0..8),
+                regs(8..16),
+                format!(
+                    "I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X}",
+                    chip8.ireg, chip8.sp, chip8.dt, chip8.st
+                ),
+                "Tab: run/pause  S: step  O: step over  U: step out".to_string(),
+                "Backspace: rewind  F1-F9: save  Shift+F1-F9: load  F12: mute  Esc: quit"
+                    .to_string(),
+            ];
+            for line in lines.iter().chain(&self.message) {
+                screen += &format!("{}{}\r\n", line, Clear(ClearType::UntilNewLine));
+            }
+        }
+        // Clear a message line left over from the last screen
+        screen += &Clear(ClearType::FromCursorDown).to_string();
+
+        screen
+    }
+
+    fn write_screen(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let screen = self.draw(chip8);
+        if screen == self.screen {
+            return Ok(());
+        }
+
+        // Clear what the other resolution left behind
+        if chip8.width() != self.width {
+            queue!(self.out, Clear(ClearType::All))?;
+            self.width = chip8.width();
+        }
+        queue!(self.out, MoveTo(0, 0), Print(&screen))?;
+        self.out.flush()?;
+        self.screen = screen;
+        Ok(())
+    }
+}
+
+fn color(palette: &Palette, px: u8) -> Color {
+    let [r, g, b] = palette.0[px as usize & 0x3];
+    Color::Rgb { r, g, b }
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Frontend for Tty {
+    fn poll_input(&mut self, chip8: &mut Chip8) -> bool {
+        let mut running = self.io_error.is_none();
+
+        loop {
+            match event::poll(Duration::ZERO).and_then(|ready| match ready {
+                true => event::read().map(Some),
+                false => Ok(None),
+            }) {
+                Ok(Some(Event::Key(key))) => running &= self.key(chip8, key),
+                // Redraw everything after the terminal is resized
+                Ok(Some(Event::Resize(..))) => {
+                    self.screen.clear();
+                    self.width = 0;
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(err) => {
+                    self.io_error = Some(err);
+                    return false;
+                }
+            }
+        }
+
+        let frame = self.frame;
+        self.held.retain(|_, &mut until| until > frame);
+        for (k, key) in chip8.keys.iter_mut().enumerate() {
+            *key = self
+                .held
+                .keys()
+                .any(|code| self.codes.get(code) == Some(&(k as u8)));
+        }
+
+        running
+    }
+
+    fn present(&mut self, chip8: &Chip8) {
+        if let Err(err) = self.write_screen(chip8) {
+            self.io_error.get_or_insert(err);
+        }
+        self.frame += 1;
+
+        // Pace frames by wall clock like the SDL frontend
+        self.deadline += Duration::from_secs(1) / TIMER_HZ;
+        let now = Instant::now();
+        if self.deadline > now {
+            std::thread::sleep(self.deadline - now);
+        } else {
+            self.deadline = now;
+        }
+    }
+
+    // The terminal bell rings once per beep
+    fn play_tone(&mut self) {
+        if !self.beeping && !self.muted {
+            let _ = execute!(self.out, Print('\x07'));
+        }
+        self.beeping = true;
+    }
+
+    fn stop_tone(&mut self) {
+        self.beeping = false;
+    }
+
+    // Errors are shown in the status lines; without them, quit and report them
+    fn error(&mut self, err: &Chip8Error) -> bool {
+        self.error = Some(err.to_string());
+        self.debugger
+    }
+
+    fn clear_error(&mut self) {
+        self.error = None;
+    }
+
+    fn rewind_capacity(&self) -> usize {
+        REWIND_SECONDS * TIMER_HZ as usize
+    }
+
+    fn rewinding(&self) -> bool {
+        self.held.contains_key(&KeyCode::Backspace)
+    }
+}
+
+pub fn emulate(rom_path: &Path, chip8: &mut Chip8, options: &Options) -> Result<(), Chip8Error> {
+    let mut tty = Tty::new(rom_path, options)?;
+    let result = frontend::run(chip8, &mut tty);
+    match tty.io_error.take() {
+        Some(err) => Err(err.into()),
+        None => result,
+    }
+}
@@ -1,10 +1,47 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use std::time::SystemTime;
-
-pub fn rand_byte() -> u8 {
-    let d = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("Duration since UNIX_EPOCH failed");
-    let mut rng = StdRng::seed_from_u64(d.as_secs());
-    rng.gen_range(0..255)
+use std::fmt::Debug;
+
+// The source of CXKK's random bytes. The whole generator state is a u64 so it can go in
+// save states, which keeps replays and rewinds deterministic.
+pub trait RandomSource: Debug {
+    fn next_byte(&mut self) -> u8;
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+}
+
+// SplitMix64: tiny, fast and good enough for games
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitMix64 {
+    pub state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
 }
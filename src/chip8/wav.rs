@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::audio::{Tone, ToneGenerator};
+use super::error::Chip8Error;
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+const HEADER_SIZE: u32 = 44;
+
+// Records the sound timer's tone as 16-bit mono PCM. Chip8::tick_timers feeds it one
+// frame per 1/60 s of emulated time, so recordings don't depend on how fast the host
+// runs the emulator.
+#[derive(Debug)]
+pub struct WavRecorder {
+    writer: BufWriter<File>,
+    generator: ToneGenerator,
+    buffer: Vec<f32>,
+    samples: u32,
+    // The first write error, reported by finish()
+    error: Option<io::Error>,
+}
+
+fn write_header(writer: &mut impl Write, samples: u32) -> io::Result<()> {
+    let data_size = samples * 2;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM, mono
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+    // Block align, bits per sample
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+impl WavRecorder {
+    pub fn create(path: &Path, tone: Tone) -> Result<Self, Chip8Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        // The sizes are filled in by finish()
+        write_header(&mut writer, 0)?;
+
+        Ok(Self {
+            writer,
+            generator: ToneGenerator::new(SAMPLE_RATE, tone),
+            buffer: Vec::new(),
+            samples: 0,
+            error: None,
+        })
+    }
+
+    pub fn frame(&mut self, playing: bool) {
+        if self.error.is_some() {
+            return;
+        }
+
+        self.buffer.clear();
+        self.generator.playing = playing;
+        self.generator.render_frame(&mut self.buffer);
+
+        for &sample in &self.buffer {
+            let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            if let Err(err) = self.writer.write_all(&pcm.to_le_bytes()) {
+                self.error = Some(err);
+                return;
+            }
+        }
+        self.samples += self.buffer.len() as u32;
+    }
+
+    pub fn finish(mut self) -> Result<(), Chip8Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, self.samples)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
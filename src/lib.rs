@@ -0,0 +1,3 @@
+mod chip8;
+
+pub use crate::chip8::*;
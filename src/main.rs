@@ -1,6 +1,349 @@
-use chip8::cpu::Chip8;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process;
 
-pub mod chip8;
+use clap::{Parser, Subcommand};
+use sha1_smol::Sha1;
+
+use chip8::asm;
+use chip8::config::{Config, Settings};
+use chip8::cpu::{Chip8, DEFAULT_IPS};
+use chip8::disasm;
+use chip8::error::Chip8Error;
+use chip8::frontend::{self, Headless};
+use chip8::palette::Palette;
+use chip8::quirks::Quirks;
+use chip8::screenshot;
+use chip8::trace::{self, TraceLine, Tracer};
+use chip8::utils::SplitMix64;
+use chip8::wav::WavRecorder;
+
+#[cfg(feature = "sdl")]
+use chip8::display;
+#[cfg(any(feature = "sdl", feature = "tty"))]
+use chip8::keymap::KeyMap;
+#[cfg(feature = "tty")]
+use chip8::tty;
+
+const DEFAULT_SCALE: u32 = 10;
+
+#[derive(Parser)]
+#[command(
+    name = "chip8",
+    version,
+    about = "CHIP-8, SUPER-CHIP and XO-CHIP emulator"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM
+    Run(RunArgs),
+    /// Print a ROM's disassembly
+    Disasm { rom: PathBuf },
+    /// Assemble a source file into a ROM
+    Asm {
+        source: PathBuf,
+        /// Output ROM [default: the source path with a .ch8 extension]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare two traces, or a trace against a live run of a ROM
+    TraceDiff(TraceDiffArgs),
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    rom: PathBuf,
+    /// Config file [default: $XDG_CONFIG_HOME/chip8/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Instructions per second [default: 700]
+    #[arg(long)]
+    ips: Option<u32>,
+    /// Window pixels per CHIP-8 pixel [default: 10]
+    #[arg(long)]
+    scale: Option<u32>,
+    /// Interpreter quirks preset [possible values: vip, chip48, schip, xochip]
+    #[arg(long, value_parser = parse_quirks)]
+    quirks: Option<Quirks>,
+    /// Don't open the debugger window
+    #[arg(long)]
+    no_debugger: bool,
+    /// Colour palette [possible values: classic, amber, green, gameboy]
+    #[arg(long, value_parser = parse_palette)]
+    palette: Option<Palette>,
+    /// Seed for the random number generator, for reproducible runs
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Run without a window
+    #[arg(long)]
+    headless: bool,
+    /// Draw the display in the terminal instead of a window
+    #[arg(long, conflicts_with = "headless")]
+    tty: bool,
+    /// Number of frames (1/60 s) to run headless
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+    /// Record the sound to a 16-bit WAV file, timed by emulated time
+    #[arg(long)]
+    record_audio: Option<PathBuf>,
+    /// Log every executed instruction to a file
+    #[arg(long)]
+    trace: Option<PathBuf>,
+    /// Only trace instructions in this hex address range, e.g. 200-2FF (repeatable)
+    #[arg(long, value_parser = parse_range, requires = "trace")]
+    trace_range: Vec<RangeInclusive<u16>>,
+    /// Only write the last N traced instructions, when an error or breakpoint hits
+    #[arg(long, requires = "trace")]
+    trace_last: Option<usize>,
+    /// Save the last frame as a PNG when the emulator exits
+    #[arg(long)]
+    screenshot: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct TraceDiffArgs {
+    /// Trace to compare against, e.g. from a reference emulator
+    expected: PathBuf,
+    /// Trace to check
+    #[arg(required_unless_present = "rom")]
+    actual: Option<PathBuf>,
+    /// Check a live headless run of this ROM instead of a second trace
+    #[arg(long, conflicts_with = "actual")]
+    rom: Option<PathBuf>,
+    /// Matching lines to show before the first difference
+    #[arg(long, default_value_t = 5)]
+    context: usize,
+    /// Number of frames (1/60 s) to run the ROM for
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+    /// Instructions per second for the live run [default: 700]
+    #[arg(long)]
+    ips: Option<u32>,
+    /// Interpreter quirks preset for the live run
+    #[arg(long, value_parser = parse_quirks)]
+    quirks: Option<Quirks>,
+    /// Seed for the live run's random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+fn parse_quirks(name: &str) -> Result<Quirks, String> {
+    Quirks::from_name(name).ok_or_else(|| format!("expected one of {}", Quirks::NAMES.join(", ")))
+}
+
+fn parse_palette(name: &str) -> Result<Palette, String> {
+    Palette::from_name(name).ok_or_else(|| format!("expected one of {}", Palette::NAMES.join(", ")))
+}
+
+fn parse_range(range: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let addr = |s: &str| {
+        let s = s.trim_start_matches("0x");
+        u16::from_str_radix(s, 16).map_err(|_| format!("`{}` is not a hex address", s))
+    };
+    Ok(addr(start)?..=addr(end)?)
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+fn config_path(args: &RunArgs) -> Option<PathBuf> {
+    match &args.config {
+        Some(path) if !path.is_file() => {
+            fail(format_args!("config file not found: {}", path.display()))
+        }
+        Some(path) => Some(path.clone()),
+        None => Config::path(),
+    }
+}
+
+fn run(args: RunArgs) {
+    if !args.rom.is_file() {
+        fail(format_args!(
+            "ROM not found: {} (the bundled ROMs are in src/roms/)",
+            args.rom.display()
+        ));
+    }
+    let rom = args.rom.to_string_lossy();
+    let bytes = fs::read(&args.rom)
+        .unwrap_or_else(|err| fail(format_args!("failed reading {}: {}", rom, err)));
+
+    // Built-in defaults < config file < the config's override for this ROM < flags
+    let config_path = config_path(&args);
+    let config = match &config_path {
+        Some(path) => Config::load(path).unwrap_or_else(|err| fail(err)),
+        None => Config::default(),
+    };
+
+    let mut settings = config.settings_for(&Sha1::from(&bytes).digest().bytes());
+    settings.merge(&Settings {
+        ips: args.ips,
+        scale: args.scale,
+        palette: args.palette,
+        quirks: args.quirks,
+        debugger: args.no_debugger.then_some(false),
+        ..Settings::default()
+    });
+    let scale = settings.scale.unwrap_or(DEFAULT_SCALE);
+    let palette = settings.palette.unwrap_or_default();
+
+    let mut chip8 = Chip8::new(settings.quirks.unwrap_or_default());
+    if let Some(seed) = args.seed {
+        chip8.rng = Box::new(SplitMix64::new(seed));
+    }
+    chip8.ips = settings.ips.unwrap_or(DEFAULT_IPS);
+    chip8.load_fontset();
+    if let Err(err) = chip8.load_rom_bytes(&bytes) {
+        fail(format_args!("failed loading {}: {}", rom, err));
+    }
+
+    if let Some(path) = &args.record_audio {
+        match WavRecorder::create(path, settings.tone()) {
+            Ok(recorder) => chip8.recorder = Some(recorder),
+            Err(err) => fail(format_args!("failed writing {}: {}", path.display(), err)),
+        }
+    }
+
+    if let Some(path) = &args.trace {
+        let mut tracer = Tracer::create(path)
+            .unwrap_or_else(|err| fail(format_args!("failed writing {}: {}", path.display(), err)));
+        if let Some(n) = args.trace_last {
+            tracer = tracer.with_ring(n);
+        }
+        tracer.ranges = args.trace_range.clone();
+        chip8.tracer = Some(tracer);
+    }
+
+    let result = if args.headless {
+        frontend::run(&mut chip8, &mut Headless::new(args.frames))
+    } else if args.tty {
+        run_tty(&args, &settings, palette, &mut chip8)
+    } else {
+        run_window(&args, &settings, config_path, &mut chip8)
+    };
+
+    if let (Some(path), Some(recorder)) = (&args.record_audio, chip8.recorder.take()) {
+        if let Err(err) = recorder.finish() {
+            fail(format_args!("failed writing {}: {}", path.display(), err));
+        }
+    }
+
+    if let (Some(path), Some(tracer)) = (&args.trace, chip8.tracer.take()) {
+        if let Err(err) = tracer.finish() {
+            fail(format_args!("failed writing {}: {}", path.display(), err));
+        }
+    }
+
+    if let Some(path) = &args.screenshot {
+        if let Err(err) = screenshot::write_png(&chip8, &palette, scale, path) {
+            fail(format_args!("failed writing {}: {}", path.display(), err));
+        }
+    }
+
+    if let Err(err) = result {
+        fail(err);
+    }
+}
+
+#[cfg(feature = "tty")]
+fn run_tty(
+    args: &RunArgs,
+    settings: &Settings,
+    palette: Palette,
+    chip8: &mut Chip8,
+) -> Result<(), Chip8Error> {
+    let options = tty::Options {
+        palette,
+        keys: KeyMap::default().with_overrides(&settings.keys),
+        debugger: settings.debugger.unwrap_or(true),
+    };
+    tty::emulate(&args.rom, chip8, &options)
+}
+
+#[cfg(not(feature = "tty"))]
+fn run_tty(_: &RunArgs, _: &Settings, _: Palette, _: &mut Chip8) -> Result<(), Chip8Error> {
+    fail("--tty needs the emulator built with the tty feature")
+}
+
+#[cfg(feature = "sdl")]
+fn run_window(
+    args: &RunArgs,
+    settings: &Settings,
+    config_path: Option<PathBuf>,
+    chip8: &mut Chip8,
+) -> Result<(), Chip8Error> {
+    let options = display::Options {
+        scale: settings.scale.unwrap_or(DEFAULT_SCALE),
+        palette: settings.palette.unwrap_or_default(),
+        debugger: settings.debugger.unwrap_or(true),
+        keys: KeyMap::default().with_overrides(&settings.keys),
+        buttons: KeyMap::gamepad().with_overrides(&settings.buttons),
+        dead_zone: settings.dead_zone.unwrap_or(display::DEFAULT_DEAD_ZONE),
+        tone: settings.tone(),
+        config_path,
+    };
+    chip8.emulate("chip8 emulator", &args.rom.to_string_lossy(), &options)
+}
+
+// Without SDL there is no window, so always run headless
+#[cfg(not(feature = "sdl"))]
+fn run_window(
+    args: &RunArgs,
+    _: &Settings,
+    _: Option<PathBuf>,
+    chip8: &mut Chip8,
+) -> Result<(), Chip8Error> {
+    frontend::run(chip8, &mut Headless::new(args.frames))
+}
+
+// Traces the ROM running headless; a CPU error just ends the trace
+fn live_trace(args: &TraceDiffArgs, rom: &Path) -> Vec<TraceLine> {
+    let bytes = fs::read(rom)
+        .unwrap_or_else(|err| fail(format_args!("failed reading {}: {}", rom.display(), err)));
+
+    let mut chip8 = Chip8::new(args.quirks.unwrap_or_default());
+    chip8.ips = args.ips.unwrap_or(DEFAULT_IPS);
+    if let Some(seed) = args.seed {
+        chip8.rng = Box::new(SplitMix64::new(seed));
+    }
+    chip8.load_fontset();
+    if let Err(err) = chip8.load_rom_bytes(&bytes) {
+        fail(format_args!("failed loading {}: {}", rom.display(), err));
+    }
+    chip8.tracer = Some(Tracer::buffered());
+
+    if let Err(err) = frontend::run(&mut chip8, &mut Headless::new(args.frames)) {
+        eprintln!("note: the live run stopped with an error: {}", err);
+    }
+
+    let lines = chip8.tracer.take().unwrap().take_lines();
+    trace::parse_trace(&lines.join("\n")).unwrap_or_else(|err| fail(err))
+}
+
+fn trace_diff(args: TraceDiffArgs) {
+    let expected = trace::read_trace(&args.expected).unwrap_or_else(|err| fail(err));
+    let actual = match (&args.actual, &args.rom) {
+        (Some(path), _) => trace::read_trace(path).unwrap_or_else(|err| fail(err)),
+        (None, Some(rom)) => live_trace(&args, rom),
+        (None, None) => unreachable!("clap requires one of them"),
+    };
+
+    match trace::diff(&expected, &actual, args.context) {
+        Some(report) => {
+            print!("{}", report);
+            process::exit(1);
+        }
+        None => println!("traces match ({} instructions)", expected.len()),
+    }
+}
 
 fn main() {
     /*
@@ -10,9 +353,19 @@ fn main() {
     /___\___|_|  \___/ \___/
     */
 
-    let mut chip8 = Chip8::new();
-    chip8.load_fontset();
-    chip8.load_rom("src/roms/tetris.ch8");
-
-    chip8.emulate("chip8 emulator", 32);
+    match Cli::parse().command {
+        Command::Run(args) => run(args),
+        Command::TraceDiff(args) => trace_diff(args),
+        Command::Disasm { rom } => match fs::read(&rom) {
+            Ok(bytes) => print!("{}", disasm::disassemble_rom(&bytes)),
+            Err(err) => fail(format_args!("failed reading {}: {}", rom.display(), err)),
+        },
+        Command::Asm { source, output } => {
+            let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+            let rom = asm::assemble_file(&source).unwrap_or_else(|err| fail(err));
+            if let Err(err) = fs::write(&output, rom) {
+                fail(format_args!("failed writing {}: {}", output.display(), err));
+            }
+        }
+    }
 }
@@ -0,0 +1,127 @@
+// Shared by the integration tests: runs ROMs headless and compares the final display
+// with a golden image in tests/golden. Set CHIP8_BLESS=1 to (re)write the goldens
+// instead, then check the new images by eye before committing them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::asm;
+use chip8::cpu::Chip8;
+use chip8::quirks::Quirks;
+use chip8::trace::{self, TraceLine, Tracer};
+use chip8::utils::SplitMix64;
+
+pub const PRESETS: [(&str, Quirks); 4] = [
+    ("vip", Quirks::VIP),
+    ("chip48", Quirks::CHIP48),
+    ("schip", Quirks::SCHIP),
+    ("xochip", Quirks::XOCHIP),
+];
+
+// One opcode for each Instruction variant
+pub const EVERY_INSTRUCTION: [u16; 50] = [
+    0x00E0, 0x00EE, 0x00C1, 0x00D1, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF, 0x1200, 0x2200, 0x3000,
+    0x4000, 0x5010, 0x5012, 0x5013, 0x6000, 0x7000, 0x8010, 0x8011, 0x8012, 0x8013, 0x8014, 0x8015,
+    0x8016, 0x8017, 0x801E, 0x9010, 0xA200, 0xB200, 0xC000, 0xD011, 0xE09E, 0xE0A1, 0xF000, 0xF101,
+    0xF002, 0xF007, 0xF00A, 0xF015, 0xF018, 0xF01E, 0xF029, 0xF030, 0xF033, 0xF03A, 0xF055, 0xF065,
+    0xF075, 0xF085,
+];
+
+pub fn path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+// A ROM from the repo: .ch8 files are read as is, anything else is assembled
+pub fn rom(relative: &str) -> Vec<u8> {
+    let path = path(relative);
+    if path.extension().is_some_and(|ext| ext == "ch8") {
+        fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    } else {
+        asm::assemble_file(&path).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+// A machine with the ROM loaded, seeded and tracing every instruction
+pub fn machine(rom: &[u8], quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.rng = Box::new(SplitMix64::new(0));
+    chip8.tracer = Some(Tracer::buffered());
+    chip8.load_fontset();
+    chip8.load_rom_bytes(rom).unwrap();
+    chip8
+}
+
+// Runs `frames` frames (or until the ROM exits), calling `input` with the frame number
+// before each one to set the held keys
+pub fn run_frames(chip8: &mut Chip8, frames: u32, mut input: impl FnMut(u32, &mut [bool; 16])) {
+    for frame in 0..frames {
+        if chip8.halted {
+            break;
+        }
+        input(frame, &mut chip8.keys);
+        if let Err(err) = chip8.run_frame() {
+            panic!("CPU error: {}\n{}", err, render(chip8));
+        }
+    }
+}
+
+pub fn run_with_input(
+    rom: &[u8],
+    quirks: Quirks,
+    frames: u32,
+    input: impl FnMut(u32, &mut [bool; 16]),
+) -> Chip8 {
+    let mut chip8 = machine(rom, quirks);
+    run_frames(&mut chip8, frames, input);
+    chip8
+}
+
+pub fn run(rom: &[u8], quirks: Quirks, frames: u32) -> Chip8 {
+    run_with_input(rom, quirks, frames, |_, _| {})
+}
+
+pub fn executed(chip8: &mut Chip8) -> Vec<TraceLine> {
+    let lines = chip8.tracer.as_mut().unwrap().take_lines();
+    trace::parse_trace(&lines.join("\n")).unwrap()
+}
+
+// One character per pixel: '.' when off, '#' for plane 1, '2' and '3' for the other
+// XO-CHIP planes
+pub fn render(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    for row in chip8.display[..chip8.width() * chip8.height()].chunks(chip8.width()) {
+        out.extend(row.iter().map(|&px| match px {
+            0 => '.',
+            1 => '#',
+            _ => char::from_digit(px as u32, 10).unwrap(),
+        }));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn assert_golden(name: &str, chip8: &Chip8) {
+    let path = path(&format!("tests/golden/{}.txt", name));
+    let actual = render(chip8);
+
+    if std::env::var_os("CHIP8_BLESS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "{}: {} (run with CHIP8_BLESS=1 to create it)",
+            path.display(),
+            err
+        )
+    });
+    assert!(
+        expected == actual,
+        "display doesn't match {}\nexpected:\n{}\nactual:\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
@@ -0,0 +1,137 @@
+// Runs test ROMs headless under each quirks preset and compares the final display with
+// the goldens in tests/golden. The community ROMs have known pass screens, which their
+// goldens were checked against: the IBM logo (tests/roms/ibm-logo.ch8), corax89's
+// opcode test (src/roms/test.ch8) and hap's keypad test (src/roms/keypad.ch8). The
+// goldens of the ROMs assembled from tests/roms/*.s are regression snapshots, reviewed
+// against the expected values listed at the top of each source file.
+mod common;
+
+use std::collections::HashSet;
+use std::mem::{discriminant, Discriminant};
+
+use chip8::cpu::Chip8;
+use chip8::opcodes::{self, Instruction};
+use chip8::quirks::Quirks;
+
+use common::{EVERY_INSTRUCTION, PRESETS};
+
+const FRAMES: u32 = 120;
+
+// The classic IBM logo ROM; its pass screen is the logo itself
+#[test]
+fn ibm_logo() {
+    let rom = common::rom("tests/roms/ibm-logo.ch8");
+    for (name, quirks) in PRESETS {
+        let chip8 = common::run(&rom, quirks, FRAMES);
+        common::assert_golden(&format!("ibm_logo_{}", name), &chip8);
+    }
+}
+
+// Every entry of the pass screen reads OK
+#[test]
+fn corax_opcode_test() {
+    let rom = common::rom("src/roms/test.ch8");
+    for (name, quirks) in PRESETS {
+        let chip8 = common::run(&rom, quirks, FRAMES);
+        common::assert_golden(&format!("corax_{}", name), &chip8);
+    }
+}
+
+// keypad.ch8 draws the keypad, waits for a key with FX0A and highlights it for 16
+// frames. The key is pressed for one frame once the keypad is drawn. It was written for
+// CHIP-48 era interpreters and only draws the keypad properly with their quirks.
+fn press(key: u8, quirks: Quirks) -> Chip8 {
+    let rom = common::rom("src/roms/keypad.ch8");
+    common::run_with_input(&rom, quirks, 44, |frame, keys| {
+        keys[key as usize] = frame == 40;
+    })
+}
+
+#[test]
+fn keypad() {
+    for (name, quirks) in [("chip48", Quirks::CHIP48), ("schip", Quirks::SCHIP)] {
+        common::assert_golden(&format!("keypad_5_{}", name), &press(0x5, quirks));
+        common::assert_golden(&format!("keypad_f_{}", name), &press(0xF, quirks));
+    }
+}
+
+#[test]
+fn flags() {
+    let rom = common::rom("tests/roms/flags.s");
+    for (name, quirks) in PRESETS {
+        let chip8 = common::run(&rom, quirks, FRAMES);
+        common::assert_golden(&format!("flags_{}", name), &chip8);
+    }
+}
+
+#[test]
+fn quirks() {
+    let rom = common::rom("tests/roms/quirks.s");
+    for (name, quirks) in PRESETS {
+        let chip8 = common::run(&rom, quirks, FRAMES);
+        common::assert_golden(&format!("quirks_{}", name), &chip8);
+    }
+}
+
+#[test]
+fn misc() {
+    let rom = common::rom("tests/roms/misc.s");
+    let chip8 = common::run_with_input(&rom, Quirks::default(), 30, |_, keys| keys[0x5] = true);
+
+    assert!(chip8.st > 0);
+    common::assert_golden("misc", &chip8);
+}
+
+#[test]
+fn superchip() {
+    let rom = common::rom("tests/roms/superchip.s");
+    let chip8 = common::run(&rom, Quirks::XOCHIP, FRAMES);
+
+    assert!(chip8.halted);
+    assert_eq!(chip8.pitch, 100);
+    assert_eq!(chip8.pattern[..4], [0x00, 0xFF, 0x00, 0xFF]);
+    common::assert_golden("superchip", &chip8);
+}
+
+fn variant(opcode: u16) -> Discriminant<Instruction> {
+    discriminant(&opcodes::decode(opcode, 0).unwrap())
+}
+
+#[test]
+fn every_instruction_is_covered() {
+    let runs = [
+        ("src/roms/test.ch8", Quirks::default()),
+        ("src/roms/keypad.ch8", Quirks::default()),
+        ("tests/roms/flags.s", Quirks::default()),
+        ("tests/roms/quirks.s", Quirks::default()),
+        ("tests/roms/misc.s", Quirks::default()),
+        ("tests/roms/superchip.s", Quirks::XOCHIP),
+    ];
+
+    let mut executed = HashSet::new();
+    for (path, quirks) in runs {
+        let mut chip8 =
+            common::run_with_input(&common::rom(path), quirks, FRAMES, |frame, keys| {
+                keys[0x5] = frame >= 40;
+            });
+        executed.extend(
+            common::executed(&mut chip8)
+                .iter()
+                .map(|line| variant(line.opcode)),
+        );
+    }
+
+    let all: HashSet<_> = EVERY_INSTRUCTION.iter().map(|&op| variant(op)).collect();
+    assert_eq!(
+        all.len(),
+        EVERY_INSTRUCTION.len(),
+        "duplicate variant in the list"
+    );
+
+    let missing: Vec<String> = EVERY_INSTRUCTION
+        .iter()
+        .filter(|&&op| !executed.contains(&variant(op)))
+        .map(|&op| opcodes::decode(op, 0).unwrap().to_string())
+        .collect();
+    assert!(missing.is_empty(), "never executed: {}", missing.join(", "));
+}
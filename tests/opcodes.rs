@@ -0,0 +1,401 @@
+// Per-instruction tests of Chip8::execute. Each row of the table below gives the
+// presets it applies to, the initial state, the instruction words to run and the
+// expected state, e.g.
+//
+//   add_carry(all): v0 = 0xFF, v1 = 0x01; 0x8014; expect v0 = 0x00, vf = 1;
+//
+// Fields are v0-vf, i, pc, sp, dt, st, plane, pitch, hires and halted; ram, display,
+// stack, keys, rpl and pattern take a start index and an array, e.g. ram[0x300] =
+// [1, 2]. Anything not listed starts as after Chip8::new (with the fonts loaded and a
+// fresh vblank) and isn't checked. The program is loaded at 0x200 and each of its
+// instructions runs once, in order unless it jumps or skips.
+mod common;
+
+use std::collections::HashSet;
+use std::mem::discriminant;
+
+use chip8::cpu::Chip8;
+use chip8::debugger::Watchpoint;
+use chip8::error::Chip8Error;
+use chip8::memory::{ADDR_INIT, BIG_FONTSET_ADDR_INIT, FONTSET_ADDR_INIT};
+use chip8::opcodes;
+use chip8::quirks::Quirks;
+use chip8::utils::SplitMix64;
+
+use common::{EVERY_INSTRUCTION, PRESETS};
+
+trait Values {
+    fn values(&self) -> Vec<u16>;
+}
+
+impl Values for i32 {
+    fn values(&self) -> Vec<u16> {
+        vec![*self as u16]
+    }
+}
+
+impl<const N: usize> Values for [i32; N] {
+    fn values(&self) -> Vec<u16> {
+        self.iter().map(|&v| v as u16).collect()
+    }
+}
+
+fn register(name: &str) -> Option<usize> {
+    let index = name.strip_prefix('v')?;
+    usize::from_str_radix(index, 16).ok().filter(|&r| r < 16)
+}
+
+fn set(chip8: &mut Chip8, name: &str, index: Option<usize>, values: &[u16]) {
+    let value = values[0];
+    match (name, index) {
+        ("i", None) => chip8.ireg = value,
+        ("pc", None) => chip8.pc = value,
+        ("sp", None) => chip8.sp = value as u8,
+        ("dt", None) => chip8.dt = value as u8,
+        ("st", None) => chip8.st = value as u8,
+        ("plane", None) => chip8.plane = value as u8,
+        ("pitch", None) => chip8.pitch = value as u8,
+        ("hires", None) => chip8.hires = value != 0,
+        ("halted", None) => chip8.halted = value != 0,
+        (_, None) if register(name).is_some() => chip8.reg[register(name).unwrap()] = value as u8,
+        (_, Some(start)) => {
+            for (k, &value) in values.iter().enumerate() {
+                let n = start + k;
+                match name {
+                    "ram" => chip8.ram[n] = value as u8,
+                    "display" => chip8.display[n] = value as u8,
+                    "stack" => chip8.stack[n] = value,
+                    "keys" => chip8.keys[n] = value != 0,
+                    "rpl" => chip8.rpl[n] = value as u8,
+                    "pattern" => chip8.pattern[n] = value as u8,
+                    _ => panic!("unknown field {}[]", name),
+                }
+            }
+        }
+        _ => panic!("unknown field {}", name),
+    }
+}
+
+fn get(chip8: &Chip8, name: &str, index: Option<usize>, len: usize) -> Vec<u16> {
+    let value = match (name, index) {
+        ("i", None) => chip8.ireg,
+        ("pc", None) => chip8.pc,
+        ("sp", None) => chip8.sp as u16,
+        ("dt", None) => chip8.dt as u16,
+        ("st", None) => chip8.st as u16,
+        ("plane", None) => chip8.plane as u16,
+        ("pitch", None) => chip8.pitch as u16,
+        ("hires", None) => chip8.hires as u16,
+        ("halted", None) => chip8.halted as u16,
+        (_, None) if register(name).is_some() => chip8.reg[register(name).unwrap()] as u16,
+        (_, Some(start)) => {
+            return (start..start + len)
+                .map(|n| match name {
+                    "ram" => chip8.ram[n] as u16,
+                    "display" => chip8.display[n] as u16,
+                    "stack" => chip8.stack[n],
+                    "keys" => chip8.keys[n] as u16,
+                    "rpl" => chip8.rpl[n] as u16,
+                    "pattern" => chip8.pattern[n] as u16,
+                    _ => panic!("unknown field {}[]", name),
+                })
+                .collect();
+        }
+        _ => panic!("unknown field {}", name),
+    };
+    vec![value]
+}
+
+fn machine(quirks: Quirks, program: &[u16]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.rng = Box::new(SplitMix64::new(0));
+    chip8.load_fontset();
+    let rom: Vec<u8> = program.iter().flat_map(|word| word.to_be_bytes()).collect();
+    chip8.load_rom_bytes(&rom).unwrap();
+    chip8.vblank = true;
+    chip8
+}
+
+// Runs as many instructions as the program has (F000 NNNN counts as one), stopping
+// early if the PC leaves the program
+fn run(chip8: &mut Chip8, program: &[u16]) {
+    let count = program.len() - program.iter().filter(|&&word| word == 0xF000).count();
+    let end = ADDR_INIT + 2 * program.len() as u16;
+
+    for _ in 0..count {
+        if !(ADDR_INIT..end).contains(&chip8.pc) {
+            break;
+        }
+        chip8.cycle().unwrap();
+    }
+}
+
+macro_rules! opcode_tests {
+    ($(
+        $name:ident($when:expr):
+            $($given:ident $([$given_index:expr])? = $given_value:expr),* ;
+            $($word:expr),+ ;
+            expect $($field:ident $([$index:expr])? = $value:expr),+ ;
+    )*) => {
+        $(
+            #[test]
+            fn $name() {
+                let when: fn(&Quirks) -> bool = $when;
+                let program = [$($word),+];
+
+                for (preset, quirks) in PRESETS.iter().filter(|(_, quirks)| when(quirks)) {
+                    let mut chip8 = machine(*quirks, &program);
+                    $(
+                        let index: Option<usize> = None $(.or(Some($given_index)))?;
+                        set(&mut chip8, stringify!($given), index, &$given_value.values());
+                    )*
+
+                    run(&mut chip8, &program);
+
+                    $(
+                        let index: Option<usize> = None $(.or(Some($index)))?;
+                        let expected = $value.values();
+                        let actual = get(&chip8, stringify!($field), index, expected.len());
+                        assert!(
+                            actual == expected,
+                            "{} ({}): {}{} is {:02X?}, expected {:02X?}",
+                            stringify!($name),
+                            preset,
+                            stringify!($field),
+                            index.map_or(String::new(), |n| format!("[{:#X}]", n)),
+                            actual,
+                            expected
+                        );
+                    )+
+                }
+            }
+        )*
+
+        // The first word of every row with the presets it applies to
+        fn rows() -> Vec<(u16, fn(&Quirks) -> bool)> {
+            vec![$(([$($word),+][0], $when)),*]
+        }
+    };
+}
+
+fn all(_: &Quirks) -> bool {
+    true
+}
+
+opcode_tests! {
+    cls(all): display[0] = [1, 3]; 0x00E0; expect display[0] = [0, 2];
+    cls_plane(all): plane = 2, display[0] = [1, 3]; 0x00E0; expect display[0] = [1, 1];
+    ret(all): stack[0] = [0x345], sp = 1; 0x00EE; expect pc = 0x345, sp = 0;
+
+    scroll_down(all): display[0] = [1]; 0x00C2; expect display[0] = [0], display[128] = [1];
+    scroll_up(all): display[128] = [1]; 0x00D2; expect display[0] = [1], display[128] = [0];
+    scroll_right(all): display[0] = [1]; 0x00FB; expect display[0] = [0, 0, 0, 0, 1];
+    scroll_left(all): display[4] = [1]; 0x00FC; expect display[0] = [1, 0, 0, 0, 0];
+    exit(all): ; 0x00FD; expect halted = 1;
+    low(all): hires = 1, display[0] = [1]; 0x00FE; expect hires = 0, display[0] = [0];
+    high(all): display[0] = [1]; 0x00FF; expect hires = 1, display[0] = [0];
+
+    jump(all): ; 0x1345; expect pc = 0x345;
+    call(all): ; 0x2345; expect pc = 0x345, sp = 1, stack[0] = [0x202];
+    se_imm(all): v1 = 0x42; 0x3142; expect pc = 0x204;
+    se_imm_not_equal(all): v1 = 0x42; 0x3143; expect pc = 0x202;
+    se_imm_skips_long_load(all): v1 = 0x42; 0x3142, 0xF000, 0x1234; expect pc = 0x206;
+    sne_imm(all): v1 = 0x42; 0x4143; expect pc = 0x204;
+    sne_imm_equal(all): v1 = 0x42; 0x4142; expect pc = 0x202;
+    se(all): v1 = 7, v2 = 7; 0x5120; expect pc = 0x204;
+    se_not_equal(all): v1 = 7, v2 = 8; 0x5120; expect pc = 0x202;
+
+    save_range(all): i = 0x300, v1 = 1, v2 = 2, v3 = 3; 0x5132;
+        expect ram[0x300] = [1, 2, 3], i = 0x300;
+    save_range_reversed(all): i = 0x300, v1 = 1, v2 = 2, v3 = 3; 0x5312;
+        expect ram[0x300] = [3, 2, 1];
+    load_range(all): i = 0x300, ram[0x300] = [1, 2, 3]; 0x5133;
+        expect v1 = 1, v2 = 2, v3 = 3, i = 0x300;
+    load_range_reversed(all): i = 0x300, ram[0x300] = [1, 2, 3]; 0x5313;
+        expect v3 = 1, v2 = 2, v1 = 3;
+
+    ld_imm(all): ; 0x6A42; expect va = 0x42;
+    add_imm(all): v1 = 0x10; 0x7102; expect v1 = 0x12;
+    add_imm_wraps_without_flag(all): v1 = 0xFF, vf = 0x55; 0x7102; expect v1 = 0x01, vf = 0x55;
+    ld(all): v2 = 0x42; 0x8120; expect v1 = 0x42, v2 = 0x42;
+
+    or(|q| q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8121; expect v1 = 0x0E, vf = 0;
+    or_keeps_vf(|q| !q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8121;
+        expect v1 = 0x0E, vf = 0x55;
+    and(|q| q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8122; expect v1 = 0x08, vf = 0;
+    and_keeps_vf(|q| !q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8122;
+        expect v1 = 0x08, vf = 0x55;
+    xor(|q| q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8123; expect v1 = 0x06, vf = 0;
+    xor_keeps_vf(|q| !q.vf_reset): v1 = 0x0C, v2 = 0x0A, vf = 0x55; 0x8123;
+        expect v1 = 0x06, vf = 0x55;
+
+    add(all): v1 = 0x10, v2 = 0x20, vf = 0x55; 0x8124; expect v1 = 0x30, vf = 0;
+    add_carry(all): v1 = 0xFF, v2 = 0x02; 0x8124; expect v1 = 0x01, vf = 1;
+    add_into_vf(all): vf = 0xFF, v1 = 0x02; 0x8F14; expect vf = 1;
+    sub(all): v1 = 0x30, v2 = 0x10; 0x8125; expect v1 = 0x20, vf = 1;
+    sub_equal(all): v1 = 0x42, v2 = 0x42; 0x8125; expect v1 = 0x00, vf = 1;
+    sub_borrow(all): v1 = 0x10, v2 = 0x30; 0x8125; expect v1 = 0xE0, vf = 0;
+    sub_into_vf(all): vf = 0x10, v1 = 0x30; 0x8F15; expect vf = 0;
+    subn(all): v1 = 0x10, v2 = 0x30; 0x8127; expect v1 = 0x20, vf = 1;
+    subn_borrow(all): v1 = 0x30, v2 = 0x10; 0x8127; expect v1 = 0xE0, vf = 0;
+    subn_into_vf(all): vf = 0x10, v1 = 0x30; 0x8F17; expect vf = 1;
+
+    shr_vy(|q| q.shift_vy): v1 = 0x01, v2 = 0x81; 0x8126; expect v1 = 0x40, v2 = 0x81, vf = 1;
+    shr_vx(|q| !q.shift_vy): v1 = 0x01, v2 = 0x80; 0x8126; expect v1 = 0x00, vf = 1;
+    shr_into_vf(all): vf = 0x03; 0x8FF6; expect vf = 1;
+    shl_vy(|q| q.shift_vy): v1 = 0x40, v2 = 0x81; 0x812E; expect v1 = 0x02, v2 = 0x81, vf = 1;
+    shl_vx(|q| !q.shift_vy): v1 = 0x40, v2 = 0x81; 0x812E; expect v1 = 0x80, vf = 0;
+    shl_into_vf(all): vf = 0x80; 0x8FFE; expect vf = 1;
+
+    sne(all): v1 = 1, v2 = 2; 0x9120; expect pc = 0x204;
+    sne_equal(all): v1 = 2, v2 = 2; 0x9120; expect pc = 0x202;
+    ld_i(all): ; 0xA345; expect i = 0x345;
+    jp_v0(|q| !q.jump_vx): v0 = 2, v3 = 4; 0xB300; expect pc = 0x302;
+    jp_vx(|q| q.jump_vx): v0 = 2, v3 = 4; 0xB300; expect pc = 0x304;
+    rnd(all): ; 0xC1FF; expect v1 = 0xE2;
+    rnd_masked(all): ; 0xC10F; expect v1 = 0x02;
+
+    drw(all): i = 0x300, ram[0x300] = [0xC0, 0x80], v1 = 2, v2 = 1, vf = 0x55; 0xD122;
+        expect display[66] = [1, 1, 0], display[130] = [1, 0], vf = 0;
+    drw_collision(all): i = 0x300, ram[0x300] = [0xC0], v1 = 2, v2 = 1, display[66] = [1];
+        0xD121; expect display[66] = [0, 1], vf = 1;
+    drw_wraps_start(all): i = 0x300, ram[0x300] = [0x80], v1 = 66, v2 = 33; 0xD121;
+        expect display[66] = [1];
+    drw_clips(|q| q.clip_sprites): i = 0x300, ram[0x300] = [0xC0], v1 = 63, v2 = 1; 0xD121;
+        expect display[127] = [1], display[64] = [0];
+    drw_wraps(|q| !q.clip_sprites): i = 0x300, ram[0x300] = [0xC0], v1 = 63, v2 = 1; 0xD121;
+        expect display[127] = [1], display[64] = [1];
+    drw_16x16(all): hires = 1, i = 0x300, ram[0x300] = [0xFF, 0xFF, 0x80, 0x01]; 0xD120;
+        expect display[0] = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0],
+            display[128] = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+    drw_plane_2(all): plane = 2, i = 0x300, ram[0x300] = [0x80], display[0] = [1]; 0xD001;
+        expect display[0] = [3], vf = 0;
+    drw_both_planes(all): plane = 3, i = 0x300, ram[0x300] = [0x80, 0x40]; 0xD001;
+        expect display[0] = [1, 2];
+    drw_waits_for_vblank(|q| q.display_wait): i = 0x300, ram[0x300] = [0x80]; 0xD001, 0xD001;
+        expect pc = 0x202, display[0] = [1];
+    drw_no_wait(|q| !q.display_wait): i = 0x300, ram[0x300] = [0x80]; 0xD001, 0xD001;
+        expect pc = 0x204, display[0] = [0];
+
+    skp(all): v1 = 5, keys[5] = [1]; 0xE19E; expect pc = 0x204;
+    skp_up(all): v1 = 5; 0xE19E; expect pc = 0x202;
+    sknp(all): v1 = 5; 0xE1A1; expect pc = 0x204;
+    sknp_down(all): v1 = 5, keys[5] = [1]; 0xE1A1; expect pc = 0x202;
+    skp_masks_key(all): v1 = 0xF5, keys[5] = [1]; 0xE19E; expect pc = 0x204;
+    sknp_masks_key(all): v1 = 0xF5, keys[5] = [1]; 0xE1A1; expect pc = 0x202;
+
+    ld_i_long(all): ; 0xF000, 0x1234; expect i = 0x1234, pc = 0x204;
+    plane(all): ; 0xF201; expect plane = 2;
+    audio(all): i = 0x300, ram[0x300] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        0xF002; expect pattern[0] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    ld_vx_dt(all): dt = 0x42; 0xF107; expect v1 = 0x42;
+    ld_k(all): keys[7] = [1]; 0xF10A; expect v1 = 7, pc = 0x202;
+    ld_k_f(all): keys[0xF] = [1]; 0xF10A; expect v1 = 0xF, pc = 0x202;
+    ld_k_waits(all): v1 = 0x42; 0xF10A; expect v1 = 0x42, pc = 0x200;
+    ld_dt(all): v1 = 0x42; 0xF115; expect dt = 0x42;
+    ld_st(all): v1 = 0x42; 0xF118; expect st = 0x42;
+    add_i(all): i = 0x300, v1 = 0x10, vf = 0x55; 0xF11E; expect i = 0x310, vf = 0x55;
+    ld_f(all): v1 = 0x0A; 0xF129; expect i = FONTSET_ADDR_INIT as i32 + 5 * 0xA;
+    ld_hf(all): v1 = 0x0A; 0xF130; expect i = BIG_FONTSET_ADDR_INIT as i32 + 10 * 0xA;
+    bcd(all): v1 = 254, i = 0x300; 0xF133; expect ram[0x300] = [2, 5, 4], i = 0x300;
+    pitch(all): v1 = 100; 0xF13A; expect pitch = 100;
+
+    store_increments_i(|q| q.load_store_inc): v0 = 1, v1 = 2, v2 = 3, i = 0x300; 0xF255;
+        expect ram[0x300] = [1, 2, 3, 0], i = 0x303;
+    store_increments_i_by_x(|q| !q.load_store_inc && q.load_store_inc_x):
+        v0 = 1, v1 = 2, v2 = 3, i = 0x300; 0xF255;
+        expect ram[0x300] = [1, 2, 3, 0], i = 0x302;
+    store(|q| !q.load_store_inc && !q.load_store_inc_x): v0 = 1, v1 = 2, v2 = 3, i = 0x300; 0xF255;
+        expect ram[0x300] = [1, 2, 3, 0], i = 0x300;
+    restore_increments_i(|q| q.load_store_inc): i = 0x300, ram[0x300] = [1, 2, 3, 4]; 0xF265;
+        expect v0 = 1, v1 = 2, v2 = 3, v3 = 0, i = 0x303;
+    restore_increments_i_by_x(|q| !q.load_store_inc && q.load_store_inc_x):
+        i = 0x300, ram[0x300] = [1, 2, 3, 4]; 0xF265;
+        expect v0 = 1, v1 = 2, v2 = 3, v3 = 0, i = 0x302;
+    restore(|q| !q.load_store_inc && !q.load_store_inc_x):
+        i = 0x300, ram[0x300] = [1, 2, 3, 4]; 0xF265;
+        expect v0 = 1, v1 = 2, v2 = 3, v3 = 0, i = 0x300;
+    save_flags(all): v0 = 1, v1 = 2, v2 = 3; 0xF175; expect rpl[0] = [1, 2, 0];
+    load_flags(all): rpl[0] = [1, 2, 3]; 0xF185; expect v0 = 1, v1 = 2, v2 = 0;
+}
+
+// Every instruction needs at least one row under every preset
+#[test]
+fn every_instruction_has_a_test() {
+    for (preset, quirks) in PRESETS {
+        let tested: HashSet<_> = rows()
+            .into_iter()
+            .filter(|(_, when)| when(&quirks))
+            .map(|(word, _)| discriminant(&opcodes::decode(word, 0).unwrap()))
+            .collect();
+
+        let missing: Vec<String> = EVERY_INSTRUCTION
+            .iter()
+            .map(|&word| opcodes::decode(word, 0).unwrap())
+            .filter(|instruction| !tested.contains(&discriminant(instruction)))
+            .map(|instruction| instruction.to_string())
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "untested with {}: {}",
+            preset,
+            missing.join(", ")
+        );
+    }
+}
+
+// Instructions in the last word of memory leave the PC wrapped round to 0, so anything
+// stepping it back must wrap too rather than overflow
+#[test]
+fn pc_wraps_at_the_end_of_memory() {
+    let at_end = |quirks, word: u16| {
+        let mut chip8 = Chip8::new(quirks);
+        chip8.ram[0xFFFE..].copy_from_slice(&word.to_be_bytes());
+        chip8.pc = 0xFFFE;
+        let result = chip8.cycle();
+        (chip8, result)
+    };
+
+    let (chip8, result) = at_end(Quirks::default(), 0xF10A);
+    assert!(result.is_ok());
+    assert_eq!(chip8.pc, 0xFFFE);
+
+    let (chip8, result) = at_end(Quirks::VIP, 0xD121);
+    assert!(result.is_ok());
+    assert_eq!(chip8.pc, 0xFFFE);
+
+    let (_, result) = at_end(Quirks::default(), 0x00EE);
+    assert!(matches!(
+        result,
+        Err(Chip8Error::StackUnderflow { pc: 0xFFFE })
+    ));
+
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.ram[0xFFFE..].copy_from_slice(&0x2345u16.to_be_bytes());
+    chip8.pc = 0xFFFE;
+    chip8.sp = chip8.stack.len() as u8;
+    let result = chip8.cycle();
+    assert!(matches!(
+        result,
+        Err(Chip8Error::StackOverflow { pc: 0xFFFE })
+    ));
+}
+
+// Skipping an instruction doesn't fetch it, so it mustn't trip a read watchpoint on it
+#[test]
+fn skip_doesnt_trip_watchpoints() {
+    for word in [0x0000, 0xF000] {
+        let mut chip8 = machine(Quirks::XOCHIP, &[0x3000, word, 0x0000, 0x0000]);
+        chip8.debugger.toggle_watchpoint(Watchpoint {
+            start: 0x202,
+            end: 0x205,
+            read: true,
+            write: false,
+        });
+        chip8.cycle().unwrap();
+
+        assert_eq!(chip8.pc, if word == 0xF000 { 0x206 } else { 0x204 });
+        assert!(chip8.debugger.watch_hit.is_none());
+        assert!(!chip8.debugger.paused);
+    }
+}
@@ -0,0 +1,124 @@
+; Arithmetic and logic results with their VF, one entry each:
+;
+;   ADD      0x10 + 0x20           30 00
+;   ADD      0xFF + 0x02           01 01    carry
+;   SUB      0x30 - 0x10           20 01
+;   SUB      0x10 - 0x30           E0 00    borrow
+;   SUBN     0x30 - 0x10           20 01
+;   SUBN     0x10 - 0x30           E0 00    borrow
+;   SUB      0x42 - 0x42           00 01    equal is not a borrow
+;   ADD      VF = 0xFF + 0x02      01 --    VF holds the carry, not the sum
+;   SHR      0x05                  02 01
+;   SHL      0x81                  02 01
+;   OR       0x0C | 0x0A           0E VF    VF is 00 with the vf_reset quirk, else 55
+;   AND      0x0C & 0x0A           08 VF
+;   XOR      0x0C ^ 0x0A           06 VF
+;   ADD imm  0xFF + 0x02           01 55    7XKK never touches VF
+
+    CLS
+    LD VD, 0
+    LD VE, 0
+
+    LD V0, 0x10
+    LD V1, 0x20
+    ADD V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0xFF
+    LD V1, 0x02
+    ADD V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x30
+    LD V1, 0x10
+    SUB V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x10
+    LD V1, 0x30
+    SUB V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x10
+    LD V1, 0x30
+    SUBN V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x30
+    LD V1, 0x10
+    SUBN V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x42
+    LD V1, 0x42
+    SUB V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD VF, 0xFF
+    LD V1, 0x02
+    ADD VF, V1
+    LD V2, VF
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x05
+    SHR V0, V0
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x81
+    SHL V0, V0
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x0C
+    LD V1, 0x0A
+    LD VF, 0x55
+    OR V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x0C
+    LD V1, 0x0A
+    LD VF, 0x55
+    AND V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x0C
+    LD V1, 0x0A
+    LD VF, 0x55
+    XOR V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0xFF
+    LD VF, 0x55
+    ADD V0, 0x02
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+end:
+    JP end
+
+include "show.s"
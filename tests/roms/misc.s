@@ -0,0 +1,41 @@
+; Timers, keys and random numbers, run with key 5 held:
+;
+;   30 2F    DT read back right after LD DT, then after the next timer tick
+;   01 01    SKNP doesn't skip for the held key 5, SKP doesn't skip for key 6
+;   XX 0X    RND with masks FF and 0F, from the test's fixed seed
+;
+; ST is set to FF for the test to check.
+
+    CLS
+    LD VD, 0
+    LD VE, 0
+
+    LD V0, 0x30
+    LD DT, V0
+    LD V2, DT
+    LD V0, 0xFF
+    LD ST, V0
+wait:
+    LD V3, DT
+    SNE V3, V2
+    JP wait
+    CALL show
+
+    LD V0, 5
+    LD V2, 0
+    SKNP V0
+    LD V2, 1
+    LD V0, 6
+    LD V3, 0
+    SKP V0
+    LD V3, 1
+    CALL show
+
+    RND V2, 0xFF
+    RND V3, 0x0F
+    CALL show
+
+end:
+    JP end
+
+include "show.s"
@@ -0,0 +1,67 @@
+; One entry per quirk, each showing a value that depends on it:
+;
+;   shift_vy         SHR V0, V1 with V0 = 01, V1 = 80    40 00 shifted Vy, else 00 01
+;   load_store_inc   byte at I after LD [I], V1          CC with the quirk, 22 with
+;                    with V0 = 11, V1 = 22                load_store_inc_x, else 11
+;   jump_vx          JP V0, table with V0 = 0, V2 = 2    0B jumped to XNN + V2, else 0A
+;   vf_reset         VF after OR with VF = 55            00 with the quirk, else 55
+;   clip_sprites     an 8 pixel wide bar drawn at x = 60 on the bottom row, which wraps
+;                    round to the left edge without the quirk
+
+    CLS
+    LD VD, 0
+    LD VE, 0
+
+    LD V0, 0x01
+    LD V1, 0x80
+    SHR V0, V1
+    LD V2, V0
+    LD V3, VF
+    CALL show
+
+    LD V0, 0x11
+    LD V1, 0x22
+    LD I, buffer
+    LD [I], V1
+    LD V0, [I]
+    LD V2, V0
+    LD V3, 0
+    CALL show
+
+    LD V0, 0
+    LD V2, 2
+    JP V0, table
+table:
+    JP jumped_nnn
+    JP jumped_xnn
+jumped_nnn:
+    LD V2, 0x0A
+    JP jump_done
+jumped_xnn:
+    LD V2, 0x0B
+jump_done:
+    LD V3, 0
+    CALL show
+
+    LD V0, 0x0C
+    LD V1, 0x0A
+    LD VF, 0x55
+    OR V0, V1
+    LD V2, VF
+    LD V3, 0
+    CALL show
+
+    LD V0, 60
+    LD V1, 30
+    LD I, bar
+    DRW V0, V1, 2
+
+end:
+    JP end
+
+buffer:
+    db 0xAA, 0xBB, 0xCC
+bar:
+    db 0xFF, 0xFF
+
+include "show.s"
@@ -0,0 +1,35 @@
+; Shared by the test ROMs: prints results as small hex digits, two result bytes per
+; entry and three entries per row.
+;
+;   show    prints V2 and V3 (usually a result and its VF) at the cursor (VD, VE)
+;   hex     prints VA as two digits at the cursor
+;
+; Uses VA-VC as scratch, so tests keep their values in V0-V9.
+
+show:
+    LD VA, V2
+    CALL hex
+    LD VA, V3
+    CALL hex
+    ADD VD, 2
+    SE VD, 66
+    RET
+    LD VD, 0
+    ADD VE, 6
+    RET
+
+hex:
+    LD VB, VA
+    SHR VB, VB
+    SHR VB, VB
+    SHR VB, VB
+    SHR VB, VB
+    LD F, VB
+    DRW VD, VE, 5
+    ADD VD, 5
+    LD VC, 0x0F
+    AND VC, VA
+    LD F, VC
+    DRW VD, VE, 5
+    ADD VD, 5
+    RET
@@ -0,0 +1,73 @@
+; SUPER-CHIP and XO-CHIP instructions, run in hi-res with the xochip preset:
+;
+;   - a big font "A" and a 16x16 outlined square, scrolled down 1 and right 4
+;   - a filled square drawn on plane 2 only
+;   - 12 34: V0-V1 saved to and loaded back from the RPL flags
+;   - 78 56: V4-V5 saved with SAVE and loaded in reverse order with LOAD V3 - V2
+;
+; It then loads an audio pattern and pitch, which the test checks, and exits.
+
+    LOW
+    HIGH
+    CLS
+
+    LD V0, 0x0A
+    LD HF, V0
+    LD V0, 0
+    LD V1, 0
+    DRW V0, V1, 10
+    LD I, outline
+    LD V0, 16
+    DRW V0, V1, 0
+
+    SCD 2
+    SCR
+    SCR
+    SCL
+    SCU 1
+
+    PLANE 2
+    LD I, filled
+    LD V0, 40
+    DRW V0, V1, 0
+    PLANE 1
+
+    LD VD, 0
+    LD VE, 24
+
+    LD V0, 0x12
+    LD V1, 0x34
+    LD R, V1
+    LD V0, 0
+    LD V1, 0
+    LD V1, R
+    LD V2, V0
+    LD V3, V1
+    CALL show
+
+    LD V4, 0x56
+    LD V5, 0x78
+    LD I, long scratch
+    SAVE V4 - V5
+    LOAD V3 - V2
+    CALL show
+
+    LD I, pattern
+    AUDIO
+    LD V0, 100
+    PITCH V0
+    EXIT
+
+outline:
+    dw 0xFFFF, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001
+    dw 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0xFFFF
+filled:
+    dw 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF
+    dw 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF
+pattern:
+    db 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF
+    db 0x0F, 0x0F, 0x0F, 0x0F, 0xF0, 0xF0, 0xF0, 0xF0
+scratch:
+    db 0, 0
+
+include "show.s"
@@ -0,0 +1,43 @@
+// Traces of live runs, as compared by trace-diff --rom
+mod common;
+
+use chip8::frontend::{self, Headless};
+use chip8::quirks::Quirks;
+use chip8::trace;
+
+// The trace of a run that ends in a CPU error keeps everything up to the failing
+// instruction
+#[test]
+fn trace_ends_at_cpu_error() {
+    let mut chip8 = common::machine(&[0x60, 0x01, 0x61, 0x02, 0x00, 0xEE], Quirks::default());
+    let result = frontend::run(&mut chip8, &mut Headless::new(10));
+    assert!(result.is_err());
+
+    let expected = trace::parse_trace(
+        "1 0200 6001 V=00000000000000000000000000000000 I=0000 SP=00 DT=00 ST=00 ; LD V0, 0x01
+2 0202 6102 V=01000000000000000000000000000000 I=0000 SP=00 DT=00 ST=00 ; LD V1, 0x02
+3 0204 00EE V=01020000000000000000000000000000 I=0000 SP=00 DT=00 ST=00 ; RET",
+    )
+    .unwrap();
+    let actual = common::executed(&mut chip8);
+    assert_eq!(trace::diff(&expected, &actual, 5), None);
+}
+
+// A DXYN waiting for vblank is traced and counted once, when it finally draws
+#[test]
+fn display_wait_is_traced_once() {
+    let rom = [0xA2, 0x08, 0xD0, 0x01, 0xD0, 0x01, 0x12, 0x06, 0x80];
+    let mut chip8 = common::run(&rom, Quirks::VIP, 3);
+    let lines = common::executed(&mut chip8);
+
+    let draws: Vec<u16> = lines
+        .iter()
+        .filter(|line| line.opcode == 0xD001)
+        .map(|line| line.pc)
+        .collect();
+    assert_eq!(draws, [0x202, 0x204]);
+    assert!(lines
+        .iter()
+        .enumerate()
+        .all(|(n, line)| line.cycle == n as u64 + 1));
+}